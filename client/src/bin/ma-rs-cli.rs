@@ -0,0 +1,286 @@
+#![allow(clippy::needless_return)]
+
+//! an interactive terminal client, `ma-rs-cli [address]` connects to 127.0.0.1:6969 by default
+//!
+//! every protocol command can be typed as is, `scan` draws the cells ahead and `map` draws every cell scanned so far
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use ma_rs_client::{CellType, ClientError, Command, Compass, Position, RoverClient, Scan};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:6969";
+const HISTORY_FILE: &str = ".ma_rs_history";
+/// commands handled by the cli itself
+const LOCAL_COMMANDS: [&str; 2] = ["help", "quit"];
+
+const HELP: &str = "\
+login <username> <password>   log in, a new username creates a rover
+forward, turnleft, turnright  drive
+dig                           dig out the cell in front
+scan                          show the cells ahead
+scanheight                    show the heights of the cells ahead
+position, energy              show the state of the rover
+map                           show every cell scanned so far
+disconnect                    log out
+quit                          leave";
+
+/// completes the command at the start of the line
+struct Commands;
+
+impl Completer for Commands {
+    type Candidate = &'static str;
+
+    fn complete(&self, line: &str, pos: usize, _: &Context<'_>) -> rustyline::Result<(usize, Vec<&'static str>)> {
+        let typed = &line[..pos];
+        if typed.contains(' ') {
+            return Ok((pos, vec![]));
+        }
+
+        let candidates = Command::NAMES.iter().chain(LOCAL_COMMANDS.iter())
+            .filter(|name| name.starts_with(typed))
+            .copied()
+            .collect();
+        return Ok((0, candidates));
+    }
+}
+
+impl Hinter for Commands {
+    type Hint = String;
+}
+
+impl Highlighter for Commands {}
+
+impl Validator for Commands {}
+
+impl Helper for Commands {}
+
+/// the cells this session has scanned, in world coordinates
+#[derive(Default)]
+struct Chart {
+    cells: HashMap<(i32, i32), CellType>,
+    rover: Option<Position>,
+}
+
+impl Chart {
+    fn record(&mut self, position: Position, scan: &Scan) {
+        let cells = scan.far.iter().chain(scan.near.iter());
+        for ((dx, dy), cell_type) in Scan::offsets(position.direction).into_iter().zip(cells) {
+            self.cells.insert((position.x + dx, position.y + dy), *cell_type);
+        }
+        self.rover = Some(position);
+    }
+
+    fn render(&self, color: bool) -> String {
+        let rover = match self.rover {
+            Some(rover) => rover,
+            None => return "nothing scanned yet".to_owned(),
+        };
+
+        let coords = || self.cells.keys().copied().chain(std::iter::once((rover.x, rover.y)));
+        let min_x = coords().map(|(x, _)| x).min().unwrap();
+        let max_x = coords().map(|(x, _)| x).max().unwrap();
+        let min_y = coords().map(|(_, y)| y).min().unwrap();
+        let max_y = coords().map(|(_, y)| y).max().unwrap();
+
+        let mut out = format!("x:{}..{} y:{}..{}", min_x, max_x, min_y, max_y);
+        for y in min_y..=max_y {
+            out.push('\n');
+            for x in min_x..=max_x {
+                if (x, y) == (rover.x, rover.y) {
+                    out += &paint(Some(CellType::Rover), arrow(rover.direction), color);
+                } else {
+                    out += &paint(self.cells.get(&(x, y)).copied(), "", color);
+                }
+            }
+        }
+        return out;
+    }
+}
+
+fn arrow(direction: Compass) -> &'static str {
+    return match direction {
+        Compass::North => "^",
+        Compass::East => ">",
+        Compass::South => "v",
+        Compass::West => "<",
+    };
+}
+
+/// one cell two characters wide, `label` replaces the cell symbol, unknown cells are left blank
+fn paint(cell_type: Option<CellType>, label: &str, color: bool) -> String {
+    let cell_type = match cell_type {
+        Some(cell_type) => cell_type,
+        None if color => return "  ".to_owned(),
+        None => return "? ".to_owned(),
+    };
+
+    let symbol = if label.is_empty() { cell_type.to_string() } else { label.to_owned() };
+    if !color {
+        return format!("{} ", symbol);
+    }
+
+    let [r, g, b] = cell_type.rgb();
+    // dark text on bright cells and the other way around
+    let luminance = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+    let foreground = if luminance > 128.0 { 30 } else { 97 };
+    return format!("\x1b[{};48;2;{};{};{}m{} \x1b[0m", foreground, r, g, b, symbol);
+}
+
+/// the scan the way the rover sees it, the far row on top and the rover at the bottom
+fn render_scan(scan: &Scan, direction: Compass, color: bool) -> String {
+    let mut grid = scan.grid();
+    // the scan is in map order, which is right to left for a rover looking south or west
+    if matches!(direction, Compass::South | Compass::West) {
+        for row in grid.iter_mut() {
+            row.reverse();
+        }
+    }
+
+    let mut out = String::new();
+    for row in grid {
+        for cell_type in row {
+            out += &match cell_type {
+                Some(cell_type) => paint(Some(cell_type), "", color),
+                None => "  ".to_owned(),
+            };
+        }
+        out.push('\n');
+    }
+    out += "    ";
+    out += &paint(Some(CellType::Rover), arrow(direction), color);
+    return out;
+}
+
+/// runs one line, only fails when the connection is lost
+async fn run(client: &mut RoverClient, chart: &mut Chart, command: Command, color: bool) -> Result<(), ClientError> {
+    let result = match command {
+        Command::Login { username, password } => {
+            let result = client.login(&username, &password).await;
+            if result.is_ok() {
+                *chart = Chart::default();
+                println!("logged in as {}", username);
+            }
+            result
+        },
+        Command::Disconnect => {
+            *chart = Chart::default();
+            client.logout().await
+        },
+        Command::Forward => client.forward().await,
+        Command::TurnLeft => client.rotate(false).await,
+        Command::TurnRight => client.rotate(true).await,
+        Command::Dig => client.dig().await,
+        Command::Position => client.position().await.map(|position| {
+            chart.rover = Some(position);
+            println!("{}", position);
+        }),
+        Command::Energy => client.energy().await.map(|energy| println!("{}", energy)),
+        Command::ScanHeight => client.scan_height().await.map(|heights| {
+            let heights: Vec<String> = heights.iter().map(|height| height.to_string()).collect();
+            println!("{}\n{}", heights[..5].join(" "), heights[5..].join(" "));
+        }),
+        Command::Scan => {
+            let position = client.position().await;
+            match position {
+                Ok(position) => client.scan().await.map(|scan| {
+                    chart.record(position, &scan);
+                    println!("{}", render_scan(&scan, position.direction, color));
+                }),
+                Err(error) => Err(error),
+            }
+        },
+        Command::Map => client.position().await.map(|position| {
+            chart.rover = Some(position);
+            println!("{}", chart.render(color));
+        }),
+    };
+
+    return match result {
+        Err(error @ (ClientError::Io(_) | ClientError::Closed | ClientError::Kicked)) => Err(error),
+        Err(error) => {
+            println!("{}", error);
+            Ok(())
+        },
+        Ok(()) => Ok(()),
+    };
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let address = std::env::args().nth(1).unwrap_or(DEFAULT_ADDRESS.to_owned());
+    // https://no-color.org
+    let color = std::env::var_os("NO_COLOR").is_none();
+
+    let mut client = match RoverClient::connect(&address).await {
+        Ok(client) => client,
+        Err(error) => {
+            eprintln!("could not connect to {}: {}", address, error);
+            std::process::exit(1);
+        },
+    };
+    println!("connected to {}, type help for the commands", address);
+
+    let mut editor: Editor<Commands, DefaultHistory> = Editor::new().expect("the terminal is not usable");
+    editor.set_helper(Some(Commands));
+    let history = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+    if let Some(history) = &history {
+        let _ = editor.load_history(history);
+    }
+
+    let mut chart = Chart::default();
+    loop {
+        for message in client.messages() {
+            println!("server: {}", message);
+        }
+
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            Err(error) => {
+                eprintln!("{}", error);
+                break;
+            },
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        // the history file would keep the password
+        if !line.starts_with("login ") {
+            let _ = editor.add_history_entry(line);
+        }
+
+        match line {
+            "help" => {
+                println!("{}", HELP);
+                continue;
+            },
+            "quit" => break,
+            _ => {},
+        }
+
+        let command = match Command::parse(line) {
+            Ok(command) => command,
+            Err(error) => {
+                println!("{}", error);
+                continue;
+            },
+        };
+
+        if let Err(error) = run(&mut client, &mut chart, command, color).await {
+            println!("{}", error);
+            break;
+        }
+    }
+
+    if let Some(history) = &history {
+        let _ = editor.save_history(history);
+    }
+}
@@ -0,0 +1,228 @@
+#![allow(clippy::needless_return)]
+
+//! drives the tcp server with many simulated rovers and reports throughput and latency percentiles
+//!
+//! `ma-rs-load [--address 127.0.0.1:6969] [--clients 1000] [--duration 30] [--think 100] [--ramp 200]`
+//!
+//! every rover logs in as `load-<n>` and then loops through the same command mix, so runs can be compared.
+//! all rovers come from one address, so the server has to allow that with `MA_RS_LIMITS=ip:0`
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+use ma_rs_client::{Command, RoverClient};
+
+const USAGE: &str = "usage: ma-rs-load [--address host:port] [--clients n] [--duration seconds] [--think milliseconds] [--ramp connections per second]";
+
+/// roughly what a player does: scan a lot, drive, turn now and then and dig what is in the way
+const MIX: [Command; 20] = [
+    Command::Scan, Command::Forward, Command::Forward, Command::Position,
+    Command::Scan, Command::TurnRight, Command::Forward, Command::Dig,
+    Command::Scan, Command::Energy, Command::Forward, Command::Forward,
+    Command::Scan, Command::TurnLeft, Command::Forward, Command::Dig,
+    Command::Position, Command::Scan, Command::ScanHeight, Command::Forward,
+];
+
+/// percentiles in the report
+const PERCENTILES: [f64; 4] = [50.0, 90.0, 99.0, 99.9];
+
+#[derive(Debug)]
+struct Options {
+    address: String,
+    clients: usize,
+    duration: Duration,
+    /// pause between two commands of one rover
+    think: Duration,
+    /// new connections per second
+    ramp: u32,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+        let mut options = Options {
+            address: "127.0.0.1:6969".to_owned(),
+            clients: 1000,
+            duration: Duration::from_secs(30),
+            think: Duration::from_millis(100),
+            ramp: 200,
+        };
+
+        while let Some(flag) = args.next() {
+            let value = args.next().ok_or(format!("{} needs a value", flag))?;
+            let number = || value.parse::<u64>().map_err(|_| format!("{} is not a number", value));
+            match flag.as_str() {
+                "--address" => options.address = value.clone(),
+                "--clients" => options.clients = number()? as usize,
+                "--duration" => options.duration = Duration::from_secs(number()?),
+                "--think" => options.think = Duration::from_millis(number()?),
+                "--ramp" => options.ramp = number()?.max(1) as u32,
+                _ => return Err(format!("unknown option {}", flag)),
+            }
+        }
+        return Ok(options);
+    }
+}
+
+/// what one rover saw, merged into the report at the end
+#[derive(Debug, Default)]
+struct Stats {
+    connected: bool,
+    commands: u64,
+    /// per command that has a reply, login included
+    latencies: HashMap<&'static str, Vec<Duration>>,
+    errors: HashMap<String, u64>,
+}
+
+impl Stats {
+    fn merge(&mut self, other: Stats) {
+        self.commands += other.commands;
+        for (command, latencies) in other.latencies {
+            self.latencies.entry(command).or_default().extend(latencies);
+        }
+        for (error, count) in other.errors {
+            *self.errors.entry(error).or_insert(0) += count;
+        }
+    }
+}
+
+/// one simulated rover, plays until `deadline` or the first error
+async fn rover(index: usize, options: &Options, deadline: Instant) -> Stats {
+    let mut stats = Stats::default();
+
+    let mut client = match RoverClient::connect(&options.address).await {
+        Ok(client) => client,
+        Err(error) => {
+            stats.errors.insert(format!("connect: {}", error), 1);
+            return stats;
+        },
+    };
+    stats.connected = true;
+
+    let started = Instant::now();
+    if let Err(error) = client.login(&format!("load-{}", index), "load").await {
+        stats.errors.insert(format!("login: {}", error), 1);
+        return stats;
+    }
+    stats.latencies.entry("login").or_default().push(started.elapsed());
+    stats.commands += 1;
+
+    // every rover starts somewhere else in the mix so the server does not see them in lockstep
+    for command in MIX.iter().cycle().skip(index % MIX.len()) {
+        if Instant::now() >= deadline {
+            break;
+        }
+
+        let started = Instant::now();
+        let result = match command {
+            Command::Forward => client.forward().await,
+            Command::TurnLeft => client.rotate(false).await,
+            Command::TurnRight => client.rotate(true).await,
+            Command::Dig => client.dig().await,
+            Command::Scan => client.scan().await.map(|_| ()),
+            Command::ScanHeight => client.scan_height().await.map(|_| ()),
+            Command::Position => client.position().await.map(|_| ()),
+            Command::Energy => client.energy().await.map(|_| ()),
+            Command::Login { .. } | Command::Disconnect | Command::Map => Ok(()),
+        };
+        stats.commands += 1;
+
+        match result {
+            Ok(()) if command.replies() => stats.latencies.entry(command.name()).or_default().push(started.elapsed()),
+            Ok(()) => {},
+            Err(error) => {
+                *stats.errors.entry(format!("{}: {}", command.name(), error)).or_insert(0) += 1;
+                break;
+            },
+        }
+
+        if !options.think.is_zero() {
+            tokio::time::sleep(options.think).await;
+        }
+    }
+
+    return stats;
+}
+
+fn percentile(sorted: &[Duration], percentile: f64) -> Duration {
+    let index = ((sorted.len() - 1) as f64 * percentile / 100.0).round() as usize;
+    return sorted[index];
+}
+
+fn millis(duration: Duration) -> String {
+    return format!("{:.2}ms", duration.as_secs_f64() * 1000.0);
+}
+
+#[tokio::main]
+async fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}\n{}", error, USAGE);
+            std::process::exit(2);
+        },
+    };
+    println!("{} rovers against {} for {:?}, {:?} think time, {} connections per second", options.clients, options.address, options.duration, options.think, options.ramp);
+
+    let options = std::sync::Arc::new(options);
+    let started = Instant::now();
+    let deadline = started + options.duration;
+
+    let mut tasks = Vec::with_capacity(options.clients);
+    for index in 0..options.clients {
+        let options = options.clone();
+        let delay = Duration::from_secs_f64(index as f64 / options.ramp as f64);
+        tasks.push(tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            return rover(index, &options, deadline).await;
+        }));
+    }
+
+    let mut total = Stats::default();
+    let mut connected = 0;
+    for task in tasks {
+        let stats = task.await.expect("a rover panicked");
+        if stats.connected {
+            connected += 1;
+        }
+        total.merge(stats);
+    }
+    let elapsed = started.elapsed();
+
+    println!();
+    println!("connections: {} of {}", connected, options.clients);
+    println!("commands:    {} in {:.1}s, {:.1}/s", total.commands, elapsed.as_secs_f64(), total.commands as f64 / elapsed.as_secs_f64());
+
+    let mut latencies: BTreeMap<&str, Vec<Duration>> = total.latencies.into_iter().collect();
+    let all: Vec<Duration> = latencies.values().flatten().copied().collect();
+    latencies.insert("all", all);
+
+    println!();
+    print!("{:<12}{:>10}", "latency", "count");
+    for p in PERCENTILES {
+        print!("{:>12}", format!("p{}", p));
+    }
+    println!("{:>12}", "max");
+
+    for (command, mut latencies) in latencies {
+        if latencies.is_empty() {
+            continue;
+        }
+        latencies.sort();
+
+        print!("{:<12}{:>10}", command, latencies.len());
+        for p in PERCENTILES {
+            print!("{:>12}", millis(percentile(&latencies, p)));
+        }
+        println!("{:>12}", millis(*latencies.last().unwrap()));
+    }
+
+    if !total.errors.is_empty() {
+        let mut errors: Vec<(String, u64)> = total.errors.into_iter().collect();
+        errors.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+
+        println!();
+        println!("errors:");
+        for (error, count) in errors {
+            println!("{:>10}  {}", count, error);
+        }
+    }
+}
@@ -0,0 +1,248 @@
+#![allow(clippy::needless_return)]
+
+//! an async client for the rover protocol on port 6969
+//!
+//! ```no_run
+//! # async fn drive() -> Result<(), ma_rs_client::ClientError> {
+//! let mut rover = ma_rs_client::RoverClient::connect("127.0.0.1:6969").await?;
+//! rover.login("bob", "hunter2").await?;
+//! if rover.scan().await?.front() == ma_rs_client::CellType::Stone {
+//!     rover.dig().await?;
+//! }
+//! rover.forward().await?;
+//! println!("{}", rover.position().await?);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, ToSocketAddrs};
+
+pub use ma_rs_protocol::{CellType, Command, Compass, Energy, ParseError, Position, Scan};
+use ma_rs_protocol::{BANNED, KICKED, LINE_TOO_LONG, LOGIN_FAILED, LOGIN_SUCCESSFUL, MESSAGE_PREFIX, NOT_SIGNED_IN, RATE_LIMITED, TOO_MANY_CONNECTIONS, TOO_MANY_PENDING};
+
+/// how long to wait for a reply before giving up
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub enum ClientError {
+    Io(io::Error),
+    /// the server did not answer within the timeout
+    Timeout,
+    /// the server closed the connection
+    Closed,
+    NotSignedIn,
+    LoginFailed,
+    Banned,
+    Kicked,
+    /// the command was dropped, wait a moment before sending the next one
+    RateLimited,
+    /// the server hung up because a limit was broken, the reason is what it answered
+    Refused(String),
+    /// the server answered something this client does not understand
+    Protocol(ParseError),
+}
+
+impl Display for ClientError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            ClientError::Io(error) => write!(f, "{}", error),
+            ClientError::Timeout => f.write_str("the server did not answer"),
+            ClientError::Closed => f.write_str("the server closed the connection"),
+            ClientError::NotSignedIn => f.write_str(NOT_SIGNED_IN),
+            ClientError::LoginFailed => f.write_str(LOGIN_FAILED),
+            ClientError::Banned => f.write_str(BANNED),
+            ClientError::Kicked => f.write_str(KICKED),
+            ClientError::RateLimited => f.write_str(RATE_LIMITED),
+            ClientError::Refused(reason) => f.write_str(reason),
+            ClientError::Protocol(error) => write!(f, "{}", error),
+        };
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<io::Error> for ClientError {
+    fn from(error: io::Error) -> ClientError {
+        return ClientError::Io(error);
+    }
+}
+
+impl From<ParseError> for ClientError {
+    fn from(error: ParseError) -> ClientError {
+        return ClientError::Protocol(error);
+    }
+}
+
+/// one connection driving one rover, commands are sent one at a time and every reply is read before the next command
+#[derive(Debug)]
+pub struct RoverClient {
+    stream: TcpStream,
+    timeout: Duration,
+    logged_in: bool,
+    /// server messages that arrived while waiting for replies, oldest first
+    messages: Vec<String>,
+}
+
+impl RoverClient {
+    pub async fn connect(addr: impl ToSocketAddrs) -> Result<RoverClient, ClientError> {
+        let stream = TcpStream::connect(addr).await?;
+        return Ok(RoverClient { stream, timeout: DEFAULT_TIMEOUT, logged_in: false, messages: vec![] });
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// takes the server messages received so far, like broadcasts from the admins
+    pub fn messages(&mut self) -> Vec<String> {
+        return std::mem::take(&mut self.messages);
+    }
+
+    /// logs in, the rover is created on the first login with a new username
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<(), ClientError> {
+        let reply = self.ask(&Command::Login { username: username.to_owned(), password: password.to_owned() }).await?;
+
+        return match reply.as_str() {
+            LOGIN_SUCCESSFUL => {
+                self.logged_in = true;
+                Ok(())
+            },
+            LOGIN_FAILED => Err(ClientError::LoginFailed),
+            BANNED => Err(ClientError::Banned),
+            _ => Err(ClientError::Protocol(ParseError::InvalidReply(reply))),
+        };
+    }
+
+    /// logs out but keeps the connection, so another rover can log in
+    pub async fn logout(&mut self) -> Result<(), ClientError> {
+        self.send(&Command::Disconnect).await?;
+        self.logged_in = false;
+        return Ok(());
+    }
+
+    /// drives one cell ahead, nothing happens when the way is blocked or the rover is out of energy
+    pub async fn forward(&mut self) -> Result<(), ClientError> {
+        return self.send(&Command::Forward).await;
+    }
+
+    pub async fn rotate(&mut self, clockwise: bool) -> Result<(), ClientError> {
+        return self.send(&if clockwise { Command::TurnRight } else { Command::TurnLeft }).await;
+    }
+
+    /// digs out the cell in front, rock and stone give points
+    pub async fn dig(&mut self) -> Result<(), ClientError> {
+        return self.send(&Command::Dig).await;
+    }
+
+    pub async fn scan(&mut self) -> Result<Scan, ClientError> {
+        return Ok(self.ask(&Command::Scan).await?.parse()?);
+    }
+
+    /// heights of the same cells as `scan`, far row first
+    pub async fn scan_height(&mut self) -> Result<Vec<u8>, ClientError> {
+        let reply = self.ask(&Command::ScanHeight).await?;
+        return reply.split(' ')
+            .map(|height| height.parse().map_err(|_| ClientError::Protocol(ParseError::InvalidReply(reply.clone()))))
+            .collect();
+    }
+
+    pub async fn position(&mut self) -> Result<Position, ClientError> {
+        return Ok(self.ask(&Command::Position).await?.parse()?);
+    }
+
+    pub async fn energy(&mut self) -> Result<Energy, ClientError> {
+        return Ok(self.ask(&Command::Energy).await?.parse()?);
+    }
+
+    /// writes a command that has no reply
+    async fn send(&mut self, command: &Command) -> Result<(), ClientError> {
+        // the server answers `not signed in` even to commands that have no reply, which would be read as the next reply
+        if !self.logged_in {
+            return Err(ClientError::NotSignedIn);
+        }
+
+        self.stream.write_all(format!("{}\n", command).as_bytes()).await?;
+        return Ok(());
+    }
+
+    /// writes a command and reads its reply
+    async fn ask(&mut self, command: &Command) -> Result<String, ClientError> {
+        if !self.logged_in && !matches!(command, Command::Login { .. }) {
+            return Err(ClientError::NotSignedIn);
+        }
+
+        self.stream.write_all(format!("{}\n", command).as_bytes()).await?;
+
+        let mut text = String::new();
+        let mut buffer = vec![0; READ_BUFFER_SIZE];
+        loop {
+            // server messages in front of the reply, each one ends with a line ending
+            while let Some(message) = take_message(&mut text) {
+                self.received(message)?;
+            }
+            // a message without its line ending yet is still arriving
+            if !text.is_empty() && !text.starts_with(MESSAGE_PREFIX) && text != KICKED {
+                break;
+            }
+
+            let n = match tokio::time::timeout(self.timeout, self.stream.read(&mut buffer)).await {
+                Ok(read) => read?,
+                Err(_) => return Err(ClientError::Timeout),
+            };
+            if n == 0 {
+                return Err(ClientError::Closed);
+            }
+            text.push_str(&String::from_utf8_lossy(&buffer[..n]));
+        }
+
+        // messages that arrived right behind the reply, replies themselves never end with a line ending
+        let mut behind = vec![];
+        while text.ends_with('\n') {
+            let line = &text[..text.len() - 1];
+            let start = [line.rfind(MESSAGE_PREFIX), line.strip_suffix(KICKED).map(|reply| reply.len())].into_iter().flatten().max();
+            match start {
+                Some(start) => behind.push(text.split_off(start).trim_end_matches('\n').to_owned()),
+                None => break,
+            }
+        }
+        for message in behind.into_iter().rev() {
+            self.received(message)?;
+        }
+
+        return match text.as_str() {
+            RATE_LIMITED => Err(ClientError::RateLimited),
+            LINE_TOO_LONG | TOO_MANY_PENDING | TOO_MANY_CONNECTIONS => Err(ClientError::Refused(text)),
+            NOT_SIGNED_IN => {
+                self.logged_in = false;
+                Err(ClientError::NotSignedIn)
+            },
+            _ => Ok(text),
+        };
+    }
+
+    /// keeps a server message for `messages`, or fails when it says the connection is going away
+    fn received(&mut self, message: String) -> Result<(), ClientError> {
+        if message == KICKED {
+            return Err(ClientError::Kicked);
+        }
+        self.messages.push(message.strip_prefix(MESSAGE_PREFIX).unwrap_or(&message).to_owned());
+        return Ok(());
+    }
+}
+
+/// the complete server message at the start of `text` without its line ending, None if it starts with something else
+fn take_message(text: &mut String) -> Option<String> {
+    if !text.starts_with(MESSAGE_PREFIX) && !text.starts_with(KICKED) {
+        return None;
+    }
+    let end = text.find('\n')?;
+    let rest = text.split_off(end + 1);
+    let message = std::mem::replace(text, rest);
+    return Some(message.trim_end_matches('\n').to_owned());
+}
@@ -0,0 +1,348 @@
+#![allow(clippy::needless_return)]
+
+//! the types of the rover protocol on port 6969, shared by the server and the client so they can not drift apart
+//!
+//! a command is one line, either written on its own or terminated by `\n`. replies have no line ending,
+//! commands that only change the world like `forward` do not reply at all. messages the server sends on its own,
+//! `message: ...` and `kicked`, end with `\n` so they can be told apart from a reply that arrives in the same read
+
+use std::fmt::{Display, Formatter, Write};
+use std::str::FromStr;
+use strum::{EnumIter, IntoEnumIterator};
+
+pub const LOGIN_SUCCESSFUL: &str = "login successful";
+pub const LOGIN_FAILED: &str = "login failed";
+pub const NOT_SIGNED_IN: &str = "not signed in";
+pub const BANNED: &str = "banned";
+pub const KICKED: &str = "kicked";
+/// the command was dropped, the connection sends more commands than it may
+pub const RATE_LIMITED: &str = "rate limited";
+/// the server hangs up after these, the client broke a limit that can not be waited out
+pub const LINE_TOO_LONG: &str = "line too long";
+pub const TOO_MANY_PENDING: &str = "too many pending commands";
+pub const TOO_MANY_CONNECTIONS: &str = "too many connections";
+/// server messages that are not a reply to anything start with this
+pub const MESSAGE_PREFIX: &str = "message: ";
+
+/// a message the server sends on its own, with its prefix and line ending. it is always one line, line breaks become spaces
+pub fn server_message(text: &str) -> String {
+    return format!("{}{}\n", MESSAGE_PREFIX, text.replace(['\r', '\n'], " "));
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    UnknownCommand(String),
+    /// the command exists but got the wrong arguments
+    Arguments(&'static str),
+    InvalidReply(String),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            ParseError::UnknownCommand(command) => write!(f, "unknown command {:?}", command),
+            ParseError::Arguments(command) => write!(f, "wrong arguments for {}", command),
+            ParseError::InvalidReply(reply) => write!(f, "invalid reply {:?}", reply),
+        };
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// one line a client can send
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Login { username: String, password: String },
+    Disconnect,
+    Position,
+    Forward,
+    TurnLeft,
+    TurnRight,
+    Scan,
+    ScanHeight,
+    Energy,
+    Map,
+    Dig,
+}
+
+impl Command {
+    /// every command name, in the order they are listed in `Command`
+    pub const NAMES: [&'static str; 11] = ["login", "disconnect", "position", "forward", "turnleft", "turnright", "scan", "scanheight", "energy", "map", "dig"];
+
+    pub fn parse(line: &str) -> Result<Command, ParseError> {
+        let mut args = line.split(' ');
+        let name = args.next().unwrap_or("");
+        let args: Vec<&str> = args.collect();
+
+        let command = match name {
+            "login" => match args.as_slice() {
+                [username, password] => Command::Login { username: username.to_string(), password: password.to_string() },
+                _ => return Err(ParseError::Arguments("login")),
+            },
+            "disconnect" => Command::Disconnect,
+            "position" => Command::Position,
+            "forward" => Command::Forward,
+            "turnleft" => Command::TurnLeft,
+            "turnright" => Command::TurnRight,
+            "scan" => Command::Scan,
+            "scanheight" => Command::ScanHeight,
+            "energy" => Command::Energy,
+            "map" => Command::Map,
+            "dig" => Command::Dig,
+            _ => return Err(ParseError::UnknownCommand(name.to_owned())),
+        };
+
+        if !args.is_empty() && !matches!(command, Command::Login { .. }) {
+            return Err(ParseError::Arguments(command.name()));
+        }
+
+        return Ok(command);
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Command::Login { .. } => "login",
+            Command::Disconnect => "disconnect",
+            Command::Position => "position",
+            Command::Forward => "forward",
+            Command::TurnLeft => "turnleft",
+            Command::TurnRight => "turnright",
+            Command::Scan => "scan",
+            Command::ScanHeight => "scanheight",
+            Command::Energy => "energy",
+            Command::Map => "map",
+            Command::Dig => "dig",
+        };
+    }
+
+    /// false for commands that are answered with nothing
+    pub fn replies(&self) -> bool {
+        return !matches!(self, Command::Disconnect | Command::Forward | Command::TurnLeft | Command::TurnRight | Command::Dig);
+    }
+}
+
+impl Display for Command {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            Command::Login { username, password } => write!(f, "login {} {}", username, password),
+            command => f.write_str(command.name()),
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, EnumIter, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum CellType {
+    Air,
+    Rock,
+    Stone,
+    Bedrock,
+    Water,
+    Rover
+}
+
+impl CellType {
+    pub fn from_u8(value: u8) -> Option<CellType> {
+        return match value {
+            0 => Some(CellType::Air),
+            1 => Some(CellType::Rock),
+            2 => Some(CellType::Stone),
+            3 => Some(CellType::Bedrock),
+            4 => Some(CellType::Water),
+            5 => Some(CellType::Rover),
+            _ => None,
+        };
+    }
+
+    /// the cell type drawn as this character by `Display`
+    pub fn from_char(symbol: char) -> Option<CellType> {
+        return CellType::iter().find(|cell_type| cell_type.to_string().starts_with(symbol));
+    }
+
+    /// the cell type with this name, ignoring case
+    pub fn from_name(name: &str) -> Option<CellType> {
+        return CellType::iter().find(|cell_type| format!("{:?}", cell_type).eq_ignore_ascii_case(name));
+    }
+
+    /// the color of the cell on the map image and in terminal clients
+    pub fn rgb(&self) -> [u8; 3] {
+        return match self {
+            CellType::Air => [250, 165, 0],
+            CellType::Rock => [128, 100, 64],
+            CellType::Stone => [64, 64, 64],
+            CellType::Bedrock => [0, 0, 0],
+            CellType::Water => [0, 0, 255],
+            CellType::Rover => [255, 0, 0],
+        };
+    }
+}
+
+impl Display for CellType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CellType::Air => f.write_char(' '),
+            CellType::Rock => f.write_char('.'),
+            CellType::Stone => f.write_char('o'),
+            CellType::Bedrock => f.write_char('X'),
+            CellType::Rover => f.write_char('R'),
+            CellType::Water => f.write_char('W'),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, EnumIter, PartialEq, Eq, Hash)]
+pub enum Compass {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Compass {
+    /// the step taken when moving one cell in this direction
+    pub fn motion(&self) -> (i32, i32) {
+        return match self {
+            Compass::North => (0, -1),
+            Compass::East => (1, 0),
+            Compass::South => (0, 1),
+            Compass::West => (-1, 0),
+        };
+    }
+
+    pub fn rotated(&self, clockwise: bool) -> Compass {
+        return match (self, clockwise) {
+            (Compass::North, true) | (Compass::South, false) => Compass::East,
+            (Compass::East, true) | (Compass::West, false) => Compass::South,
+            (Compass::South, true) | (Compass::North, false) => Compass::West,
+            (Compass::West, true) | (Compass::East, false) => Compass::North,
+        };
+    }
+}
+
+impl FromStr for Compass {
+    type Err = ParseError;
+
+    fn from_str(name: &str) -> Result<Compass, ParseError> {
+        return Compass::iter().find(|compass| format!("{:?}", compass) == name).ok_or(ParseError::InvalidReply(name.to_owned()));
+    }
+}
+
+/// the reply to `position`, like `Position x:3 y:4 Direction:North`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+    pub direction: Compass,
+}
+
+impl FromStr for Position {
+    type Err = ParseError;
+
+    fn from_str(reply: &str) -> Result<Position, ParseError> {
+        let invalid = || ParseError::InvalidReply(reply.to_owned());
+        let fields: Vec<&str> = reply.split(' ').collect();
+
+        return match fields.as_slice() {
+            ["Position", x, y, direction] => Ok(Position {
+                x: x.strip_prefix("x:").and_then(|x| x.parse().ok()).ok_or_else(invalid)?,
+                y: y.strip_prefix("y:").and_then(|y| y.parse().ok()).ok_or_else(invalid)?,
+                direction: direction.strip_prefix("Direction:").ok_or_else(invalid)?.parse()?,
+            }),
+            _ => Err(invalid()),
+        };
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "Position x:{} y:{} Direction:{:?}", self.x, self.y, self.direction);
+    }
+}
+
+/// the reply to `energy`, like `Energy 97/100`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Energy {
+    pub energy: u32,
+    pub max: u32,
+}
+
+impl FromStr for Energy {
+    type Err = ParseError;
+
+    fn from_str(reply: &str) -> Result<Energy, ParseError> {
+        let (energy, max) = reply.strip_prefix("Energy ").and_then(|energy| energy.split_once('/')).ok_or(ParseError::InvalidReply(reply.to_owned()))?;
+
+        return match (energy.parse(), max.parse()) {
+            (Ok(energy), Ok(max)) => Ok(Energy { energy, max }),
+            _ => Err(ParseError::InvalidReply(reply.to_owned())),
+        };
+    }
+}
+
+impl Display for Energy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "Energy {}/{}", self.energy, self.max);
+    }
+}
+
+/// the reply to `scan`: the row of five cells two steps ahead and the row of three cells right in front,
+/// both in map order, left to right when the rover faces north or south and top to bottom when it faces east or west
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scan {
+    pub far: [CellType; 5],
+    pub near: [CellType; 3],
+}
+
+impl Scan {
+    /// where the scanned cells are relative to a rover facing `direction`, in the order of `far` and then `near`
+    pub fn offsets(direction: Compass) -> [(i32, i32); 8] {
+        let (dx, dy) = direction.motion();
+        // the rows run along x when the rover looks along y and the other way around
+        let (across_x, across_y) = (dy.abs(), dx.abs());
+
+        let mut offsets = [(0, 0); 8];
+        for (index, across) in (-2..3).enumerate() {
+            offsets[index] = (2 * dx + across * across_x, 2 * dy + across * across_y);
+        }
+        for (index, across) in (-1..2).enumerate() {
+            offsets[index + 5] = (dx + across * across_x, dy + across * across_y);
+        }
+        return offsets;
+    }
+
+    /// the scan as two rows of five, the near row only has cells in the middle three columns
+    pub fn grid(&self) -> [[Option<CellType>; 5]; 2] {
+        let mut near = [None; 5];
+        for (index, cell_type) in self.near.iter().enumerate() {
+            near[index + 1] = Some(*cell_type);
+        }
+        return [self.far.map(Some), near];
+    }
+
+    /// the cell straight ahead
+    pub fn front(&self) -> CellType {
+        return self.near[1];
+    }
+}
+
+impl FromStr for Scan {
+    type Err = ParseError;
+
+    fn from_str(reply: &str) -> Result<Scan, ParseError> {
+        let cells: Option<Vec<CellType>> = reply.chars().map(CellType::from_char).collect();
+
+        return match cells.as_deref() {
+            Some([a, b, c, d, e, f, g, h]) => Ok(Scan { far: [*a, *b, *c, *d, *e], near: [*f, *g, *h] }),
+            _ => Err(ParseError::InvalidReply(reply.to_owned())),
+        };
+    }
+}
+
+impl Display for Scan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for cell_type in self.far.iter().chain(self.near.iter()) {
+            write!(f, "{}", cell_type)?;
+        }
+        return Ok(());
+    }
+}
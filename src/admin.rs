@@ -0,0 +1,328 @@
+use std::collections::HashSet;
+use axum::{extract::Path, http::{header, HeaderMap, StatusCode}, response::{IntoResponse, Response}, routing::{get, post}, Json, Router};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use crate::live::{self, LiveEvent};
+use crate::planet::{CellType, Coord, Planet, PlanetError};
+use crate::rover::Rover;
+use crate::sessions::RoverHandle;
+use crate::{GameServer, Message};
+use ma_rs_protocol::{server_message, KICKED};
+
+/// something an admin can do to the running server, from the console or over http
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminCommand {
+    Clients,
+    Kick(String),
+    Ban(String),
+    Unban(String),
+    Teleport(String, Coord),
+    SetCell(Coord, CellType),
+    ResetPoints(String),
+    /// a new planet, with a random seed unless one is given
+    Regenerate(Option<u64>),
+    Broadcast(String),
+}
+
+pub const USAGE: &str = "clients | kick <username> | ban <username> | unban <username> | teleport <username> <x> <y> | setcell <x> <y> <celltype> | resetpoints <username> | regenerate [seed] | broadcast <message>";
+
+impl AdminCommand {
+    /// a console line like `teleport bob 10 20`
+    pub fn parse(line: &str) -> Result<AdminCommand, String> {
+        let mut args = line.split_whitespace();
+        let command = args.next().unwrap_or("");
+        let args: Vec<&str> = args.collect();
+
+        let coord = |x: &str, y: &str| match (x.parse(), y.parse()) {
+            (Ok(x), Ok(y)) => Ok(Coord::new(x, y)),
+            _ => Err(format!("{} {} is not a coordinate", x, y)),
+        };
+
+        return match (command, args.as_slice()) {
+            ("clients", []) => Ok(AdminCommand::Clients),
+            ("kick", [username]) => Ok(AdminCommand::Kick(username.to_string())),
+            ("ban", [username]) => Ok(AdminCommand::Ban(username.to_string())),
+            ("unban", [username]) => Ok(AdminCommand::Unban(username.to_string())),
+            ("teleport", [username, x, y]) => Ok(AdminCommand::Teleport(username.to_string(), coord(x, y)?)),
+            ("setcell", [x, y, cell_type]) => match CellType::from_name(cell_type) {
+                Some(cell_type) => Ok(AdminCommand::SetCell(coord(x, y)?, cell_type)),
+                None => Err(format!("unknown cell type {}", cell_type)),
+            },
+            ("resetpoints", [username]) => Ok(AdminCommand::ResetPoints(username.to_string())),
+            ("regenerate", []) => Ok(AdminCommand::Regenerate(None)),
+            ("regenerate", [seed]) => match seed.parse() {
+                Ok(seed) => Ok(AdminCommand::Regenerate(Some(seed))),
+                Err(_) => Err(format!("{} is not a seed", seed)),
+            },
+            ("broadcast", words) if !words.is_empty() => Ok(AdminCommand::Broadcast(words.join(" "))),
+            _ => Err(format!("usage: {}", USAGE)),
+        };
+    }
+
+    /// the body of a POST /admin/<action> request
+    fn from_json(action: &str, body: &Value) -> Result<AdminCommand, String> {
+        let username = || body["username"].as_str().map(|username| username.to_owned()).ok_or("username is required".to_owned());
+        let coord = || match (body["x"].as_i64(), body["y"].as_i64()) {
+            (Some(x), Some(y)) => match (i32::try_from(x), i32::try_from(y)) {
+                (Ok(x), Ok(y)) => Ok(Coord::new(x, y)),
+                _ => Err(format!("{} {} is not a coordinate", x, y)),
+            },
+            _ => Err("x and y are required".to_owned()),
+        };
+
+        return match action {
+            "kick" => Ok(AdminCommand::Kick(username()?)),
+            "ban" => Ok(AdminCommand::Ban(username()?)),
+            "unban" => Ok(AdminCommand::Unban(username()?)),
+            "teleport" => Ok(AdminCommand::Teleport(username()?, coord()?)),
+            "setcell" => match body["cell_type"].as_str().and_then(CellType::from_name) {
+                Some(cell_type) => Ok(AdminCommand::SetCell(coord()?, cell_type)),
+                None => Err("cell_type has to be a cell type".to_owned()),
+            },
+            "resetpoints" => Ok(AdminCommand::ResetPoints(username()?)),
+            "regenerate" => Ok(AdminCommand::Regenerate(body["seed"].as_u64())),
+            "broadcast" => match body["message"].as_str() {
+                Some(message) if !message.trim().is_empty() => Ok(AdminCommand::Broadcast(message.trim().to_owned())),
+                _ => Err("message is required".to_owned()),
+            },
+            _ => Err(format!("unknown action {}", action)),
+        };
+    }
+}
+
+/// runs the command against the live game, the same lock order as the protocol: rovers, planet
+pub async fn run(state: &GameServer, command: AdminCommand) -> Result<Value, String> {
+    match command {
+        AdminCommand::Clients => {
+            let clients: Vec<Value> = state.sessions.with(|sessions| sessions.sessions().map(|client| json!({
+                "uuid": client.uuid.to_string(),
+                "kind": match (client.bot, client.connection.is_some()) {
+                    (true, _) => "bot",
+                    (false, true) => "connection",
+                    (false, false) => "http",
+                },
+                "username": client.username,
+            })).collect()).await;
+
+            return Ok(json!(clients));
+        },
+        AdminCommand::Kick(username) => {
+            if !kick(state, &username).await {
+                return Err(format!("{} is not online", username));
+            }
+            return Ok(json!(format!("kicked {}", username)));
+        },
+        AdminCommand::Ban(username) => {
+            state.bans.lock().await.insert(username.clone());
+            kick(state, &username).await;
+            return Ok(json!(format!("banned {}", username)));
+        },
+        AdminCommand::Unban(username) => {
+            if !state.bans.lock().await.remove(&username) {
+                return Err(format!("{} is not banned", username));
+            }
+            return Ok(json!(format!("unbanned {}", username)));
+        },
+        AdminCommand::Teleport(username, coord) => {
+            if !coord.in_bounds() {
+                return Err(PlanetError::OutOfBounds(coord).to_string());
+            }
+            let rover = find(state, &username).await?;
+            let mut rover = rover.lock().await;
+
+            let planet = state.planet.read().await;
+            planet.load_around(coord);
+            if !planet.replace(coord, CellType::Air, CellType::Rover).map_err(|error| error.to_string())? {
+                return Err(format!("{} is not free", coord));
+            }
+
+            planet.set_celltype(rover.coord(), CellType::Air).map_err(|error| error.to_string())?;
+            rover.x = coord.x;
+            rover.y = coord.y;
+            rover.explored.insert(coord);
+            rover.trail.clear();
+
+            live::publish_changes(&planet, &state.live_sender);
+            let _ = state.live_sender.send(LiveEvent::rover(&rover));
+
+            return Ok(json!(format!("{} is at {}", username, coord)));
+        },
+        AdminCommand::SetCell(coord, cell_type) => {
+            if cell_type == CellType::Rover {
+                return Err("rovers can only be placed by teleporting them".to_owned());
+            }
+            if !coord.in_bounds() {
+                return Err(PlanetError::OutOfBounds(coord).to_string());
+            }
+
+            let planet = state.planet.read().await;
+            planet.load_around(coord);
+            if planet.get_cell_type(coord) == CellType::Rover {
+                return Err(format!("there is a rover at {}", coord));
+            }
+
+            planet.set_celltype(coord, cell_type).map_err(|error| error.to_string())?;
+            live::publish_changes(&planet, &state.live_sender);
+
+            return Ok(json!(format!("{} is {:?}", coord, cell_type)));
+        },
+        AdminCommand::ResetPoints(username) => {
+            find(state, &username).await?.lock().await.points = 0;
+            return Ok(json!(format!("{} has 0 points", username)));
+        },
+        AdminCommand::Regenerate(seed) => {
+            let size = state.planet.read().await.size;
+            let handles = state.sessions.with(|sessions| sessions.rovers()).await;
+            let count = handles.len();
+
+            // generating takes seconds for a large planet, so it happens before anything is locked.
+            // every spawn point is claimed before any rover moves, so a full planet leaves them all where they were
+            let (new_planet, spawnpoints) = tokio::task::spawn_blocking(move || {
+                let planet = match seed {
+                    Some(seed) => Planet::with_seed(size, seed),
+                    None => Planet::new(size),
+                };
+                let spawnpoints = (0..count).map(|_| claim_spawn(&planet)).collect::<Result<Vec<Coord>, String>>()?;
+                return Ok::<_, String>((planet, spawnpoints));
+            }).await.map_err(|error| error.to_string())??;
+
+            let mut rovers = Vec::with_capacity(handles.len());
+            for (rover, _) in handles.iter() {
+                rovers.push(rover.lock().await);
+            }
+            let mut planet = state.planet.write().await;
+
+            for (rover, spawnpoint) in rovers.iter_mut().zip(spawnpoints) {
+                respawn(rover, spawnpoint);
+            }
+
+            // viewers get the whole new board instead of every changed cell
+            new_planet.take_changes();
+            *planet = new_planet;
+            let _ = state.live_sender.send(LiveEvent::Reset);
+
+            return Ok(json!(format!("regenerated the planet with seed {}", planet.seed())));
+        },
+        AdminCommand::Broadcast(message) => {
+            let (author, data) = (state.server_uuid, server_message(&message).into_bytes());
+            state.sessions.with(move |sessions| {
+                for client in sessions.sessions() {
+                    if let Some(connection) = &client.connection {
+                        let _ = connection.sender.send(Message { author, target: client.uuid, data: data.clone(), response: None });
+                    }
+                }
+            }).await;
+
+            let _ = state.live_sender.send(LiveEvent::Notice { message });
+            return Ok(json!("sent"));
+        },
+    }
+}
+
+/// a random free cell of a fresh planet, taken by a rover cell so the next call picks another one
+fn claim_spawn(planet: &Planet) -> Result<Coord, String> {
+    let spawnpoint = planet.random_spawn(&mut rand::thread_rng()).ok_or("the new planet has no room for every rover".to_owned())?;
+    planet.load_around(spawnpoint);
+    planet.set_celltype(spawnpoint, CellType::Rover).map_err(|error| error.to_string())?;
+    return Ok(spawnpoint);
+}
+
+/// moves the rover to its claimed spawn point, everything it knew about the old planet is gone
+fn respawn(rover: &mut Rover, spawnpoint: Coord) {
+    rover.x = spawnpoint.x;
+    rover.y = spawnpoint.y;
+    rover.explored = HashSet::from([spawnpoint]);
+    rover.trail.clear();
+}
+
+/// the rover whether it is online or not
+async fn find(state: &GameServer, username: &str) -> Result<RoverHandle, String> {
+    let name = username.to_owned();
+    let rover = state.sessions.with(move |sessions| sessions.rover(&name)).await;
+    return rover.map(|(rover, _)| rover).ok_or(format!("there is no rover called {}", username));
+}
+
+/// logs the rover out and closes its connection, false if nobody is driving it
+async fn kick(state: &GameServer, username: &str) -> bool {
+    let name = username.to_owned();
+    let client = match state.sessions.with(move |sessions| sessions.logout_rover(&name)).await {
+        Some(client) => client,
+        None => return false,
+    };
+
+    println!("{:?} was kicked", username);
+
+    // http sessions have nothing to close, their next command is simply not signed in
+    if let Some(connection) = &client.connection {
+        let _ = connection.sender.send(Message { author: state.server_uuid, target: client.uuid, data: format!("{}\n", KICKED).into_bytes(), response: None });
+        connection.kick.notify_one();
+    }
+
+    return true;
+}
+
+/// reads admin commands from stdin until it closes
+pub async fn console(state: GameServer) {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let result = match AdminCommand::parse(&line) {
+            Ok(command) => run(&state, command).await,
+            Err(error) => Err(error),
+        };
+
+        match result {
+            Ok(Value::String(reply)) => println!("{}", reply),
+            Ok(reply) => println!("{}", serde_json::to_string_pretty(&reply).unwrap()),
+            Err(error) => println!("error: {}", error),
+        }
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    return (status, Json(json!({ "error": message }))).into_response();
+}
+
+fn authorized(headers: &HeaderMap, token: &str) -> bool {
+    return headers.get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|value| value.trim() == token);
+}
+
+async fn respond(state: &GameServer, command: Result<AdminCommand, String>) -> Response {
+    let command = match command {
+        Ok(command) => command,
+        Err(message) => return error(StatusCode::BAD_REQUEST, &message),
+    };
+
+    return match run(state, command).await {
+        Ok(reply) => Json(json!({ "reply": reply })).into_response(),
+        Err(message) => error(StatusCode::BAD_REQUEST, &message),
+    };
+}
+
+/// GET /admin/clients and POST /admin/<action>, every request needs `Authorization: Bearer <token>`
+pub fn router(state: GameServer, token: String) -> Router {
+    let clients_state = state.clone();
+    let clients_token = token.clone();
+
+    return Router::new()
+        .route("/admin/clients", get(move |headers: HeaderMap| async move {
+            if !authorized(&headers, &clients_token) {
+                return error(StatusCode::UNAUTHORIZED, "wrong admin token");
+            }
+            respond(&clients_state, Ok(AdminCommand::Clients)).await
+        }))
+        .route("/admin/:action", post(move |Path(action): Path<String>, headers: HeaderMap, body: Option<Json<Value>>| async move {
+            if !authorized(&headers, &token) {
+                return error(StatusCode::UNAUTHORIZED, "wrong admin token");
+            }
+            let body = body.map(|Json(body)| body).unwrap_or(Value::Null);
+            respond(&state, AdminCommand::from_json(&action, &body)).await
+        }));
+}
@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use axum::{extract::{ConnectInfo, Path}, http::{header, HeaderMap, StatusCode}, response::{IntoResponse, Response}, routing::{get, post}, Json, Router};
+use flume::Sender;
+use serde_json::{json, Value};
+use uuid::Uuid;
+use tokio::time::Instant;
+use crate::limits::{IpSlot, Limiter, Verdict};
+use crate::{GameServer, Message};
+use ma_rs_protocol::{Command, NOT_SIGNED_IN, RATE_LIMITED, TOO_MANY_CONNECTIONS};
+
+pub const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+/// a token that is not used for this long is logged out, like a tcp connection that went away
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+/// how often idle tokens are looked for
+const IDLE_CHECK: Duration = Duration::from_secs(10);
+
+/// what a logged in token keeps between requests, the same limits a tcp connection has
+#[derive(Debug)]
+struct ApiSession {
+    limiter: Limiter,
+    /// given back when the session ends
+    _slot: IpSlot,
+    last_used: Instant,
+}
+
+/// the rover protocol over http, every request goes through the same message channel as a tcp client would
+#[derive(Clone)]
+struct Api {
+    sender: Sender<Message>,
+    game: GameServer,
+    /// never locked across an await
+    sessions: Arc<Mutex<HashMap<Uuid, ApiSession>>>,
+}
+
+impl Api {
+    /// sends one protocol line as the session `token` and waits for the reply
+    async fn send(&self, token: Uuid, line: String) -> Option<String> {
+        let (response, reply) = flume::bounded::<Message>(1);
+        self.sender.send(Message { author: token, target: self.game.server_uuid, data: line.into_bytes(), response: Some(response) }).ok()?;
+
+        let reply = tokio::time::timeout(REPLY_TIMEOUT, reply.recv_async()).await.ok()?.ok()?;
+        return String::from_utf8(reply.data).ok();
+    }
+
+    /// the session from an `Authorization: Bearer <token>` header
+    fn token(headers: &HeaderMap) -> Option<Uuid> {
+        let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+        return Uuid::parse_str(value.strip_prefix("Bearer ")?.trim()).ok();
+    }
+
+    async fn login(&self, body: Value, ip: IpAddr) -> Response {
+        let (username, password) = match (body["username"].as_str(), body["password"].as_str()) {
+            (Some(username), Some(password)) => (username, password),
+            _ => return error(StatusCode::BAD_REQUEST, "username and password are required"),
+        };
+
+        if username.is_empty() || username.contains(char::is_whitespace) || password.contains(char::is_whitespace) {
+            return error(StatusCode::BAD_REQUEST, "username and password can not contain whitespace");
+        }
+
+        let slot = match self.game.ip_slots.take(ip, self.game.limits.connections_per_ip) {
+            Some(slot) => slot,
+            None => {
+                self.game.metrics.dropped();
+                return error(StatusCode::TOO_MANY_REQUESTS, TOO_MANY_CONNECTIONS);
+            },
+        };
+
+        let token = Uuid::new_v4();
+        self.game.open_session(token, None).await;
+
+        let reply = self.send(token, format!("login {} {}", username, password)).await;
+        if reply.as_deref() != Some("login successful") {
+            self.disconnect(token);
+            return error(StatusCode::UNAUTHORIZED, reply.as_deref().unwrap_or("login failed"));
+        }
+
+        self.sessions.lock().unwrap().insert(token, ApiSession { limiter: Limiter::new(self.game.limits), _slot: slot, last_used: Instant::now() });
+        return Json(json!({ "token": token.to_string() })).into_response();
+    }
+
+    /// same as a tcp connection going away, false if the api never handed out this token.
+    /// any other session, like a tcp connection an admin sees in /admin/clients, is left alone
+    fn logout(&self, token: Uuid) -> bool {
+        if self.sessions.lock().unwrap().remove(&token).is_none() {
+            return false;
+        }
+        self.disconnect(token);
+        return true;
+    }
+
+    /// the rover goes offline and the session is forgotten
+    fn disconnect(&self, token: Uuid) {
+        let _ = self.sender.send(Message { author: token, target: self.game.server_uuid, data: "disconnect".as_bytes().to_vec(), response: None });
+    }
+
+    /// logs out every token that has not been used for `IDLE_TIMEOUT`, forever
+    async fn expire(&self) {
+        loop {
+            tokio::time::sleep(IDLE_CHECK).await;
+
+            let idle: Vec<Uuid> = self.sessions.lock().unwrap().iter()
+                .filter(|(_, session)| session.last_used.elapsed() >= IDLE_TIMEOUT)
+                .map(|(token, _)| *token)
+                .collect();
+            for token in idle {
+                println!("api session {} expired", token);
+                self.logout(token);
+            }
+        }
+    }
+
+    async fn command(&self, headers: HeaderMap, command: String) -> Response {
+        let token = match Api::token(&headers) {
+            Some(token) => token,
+            None => return error(StatusCode::UNAUTHORIZED, "missing bearer token"),
+        };
+
+        if command == "login" || command == "disconnect" {
+            return error(StatusCode::NOT_FOUND, "use /api/login and /api/logout");
+        }
+
+        // tokens the api never handed out do not reach the game at all
+        let verdict = match self.sessions.lock().unwrap().get_mut(&token) {
+            Some(session) => {
+                session.last_used = Instant::now();
+                session.limiter.check(command.as_bytes())
+            },
+            None => return error(StatusCode::UNAUTHORIZED, NOT_SIGNED_IN),
+        };
+        match verdict {
+            Verdict::Accept => {},
+            Verdict::Reject(_) => {
+                self.game.metrics.rejected();
+                return error(StatusCode::TOO_MANY_REQUESTS, RATE_LIMITED);
+            },
+            Verdict::Disconnect(reason) => {
+                self.game.metrics.dropped();
+                self.logout(token);
+                return error(StatusCode::TOO_MANY_REQUESTS, &String::from_utf8_lossy(&reason));
+            },
+        }
+
+        let reply = self.send(token, command).await;
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(&token) {
+            session.limiter.replied();
+        }
+
+        return match reply {
+            Some(reply) if reply == NOT_SIGNED_IN => {
+                // kicked, the token is of no use anymore
+                self.logout(token);
+                error(StatusCode::UNAUTHORIZED, &reply)
+            },
+            Some(reply) => Json(json!({ "reply": reply })).into_response(),
+            None => error(StatusCode::GATEWAY_TIMEOUT, "the server did not answer"),
+        };
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    return (status, Json(json!({ "error": message }))).into_response();
+}
+
+/// commands that change the world are not allowed over GET, anything may prefetch or repeat a GET
+fn changes_the_world(command: &str) -> bool {
+    return matches!(Command::parse(command), Ok(Command::Forward | Command::TurnLeft | Command::TurnRight | Command::Dig));
+}
+
+/// POST /api/login, POST /api/logout, GET or POST /api/<command> for commands that only look and POST for the others.
+/// has to be called inside the tokio runtime, idle tokens are expired by a task of their own
+pub fn router(sender: Sender<Message>, game: GameServer) -> Router {
+    let api = Api { sender, game, sessions: Arc::new(Mutex::new(HashMap::new())) };
+
+    let expire = api.clone();
+    tokio::spawn(async move { expire.expire().await });
+
+    let login = api.clone();
+    let logout = api.clone();
+    let command = api.clone();
+
+    return Router::new()
+        .route("/api/login", post(move |ConnectInfo(addr): ConnectInfo<SocketAddr>, Json(body): Json<Value>| async move {
+            login.login(body, addr.ip()).await
+        }))
+        .route("/api/logout", post(move |headers: HeaderMap| async move {
+            match Api::token(&headers) {
+                Some(token) if logout.logout(token) => StatusCode::NO_CONTENT.into_response(),
+                Some(_) => error(StatusCode::UNAUTHORIZED, NOT_SIGNED_IN),
+                None => error(StatusCode::UNAUTHORIZED, "missing bearer token"),
+            }
+        }))
+        .route("/api/:command", get({
+            let command = command.clone();
+            move |Path(name): Path<String>, headers: HeaderMap| async move {
+                if changes_the_world(&name) {
+                    return error(StatusCode::METHOD_NOT_ALLOWED, &format!("{} changes the world, use POST", name));
+                }
+                command.command(headers, name).await
+            }
+        }).post(move |Path(name): Path<String>, headers: HeaderMap| async move {
+            command.command(headers, name).await
+        }));
+}
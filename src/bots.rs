@@ -0,0 +1,263 @@
+//! rovers driven by the server itself, they play through the same protocol lines as every other client
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::str::FromStr;
+use std::time::Duration;
+use flume::Sender;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use strum::{EnumIter, IntoEnumIterator};
+use uuid::Uuid;
+use crate::planet::{CellTrait, CellType, Coord};
+use crate::{GameServer, Message};
+use ma_rs_protocol::{Command, Compass, Position, Scan, LOGIN_SUCCESSFUL};
+
+/// every bot username starts with this and players can not use it
+pub const BOT_PREFIX: &str = "bot-";
+/// how long a bot waits between two moves
+pub const BOT_TICK: Duration = Duration::from_millis(250);
+/// how far the stone bot looks for a way to a known stone
+const SEARCH_LIMIT: usize = 4096;
+/// the distance between two sides of the spiral, the width of a scan
+const SPIRAL_SPACING: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter)]
+pub enum Strategy {
+    /// mostly drives straight ahead and turns at random
+    RandomWalk,
+    /// digs the nearest stone it has seen and wanders around until it sees one
+    Stone,
+    /// drives an ever growing square spiral and digs through rock and stone on the way
+    Spiral,
+}
+
+impl Strategy {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Strategy::RandomWalk => "random",
+            Strategy::Stone => "stone",
+            Strategy::Spiral => "spiral",
+        };
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Strategy, String> {
+        return Strategy::iter().find(|strategy| strategy.name() == name).ok_or_else(|| {
+            let names: Vec<&str> = Strategy::iter().map(|strategy| strategy.name()).collect();
+            format!("unknown bot strategy {}, use one of {}", name, names.join(", "))
+        });
+    }
+}
+
+/// a list like `random:2,stone,spiral:3`, a strategy without a count is one bot
+pub fn parse_config(config: &str) -> Result<Vec<(Strategy, usize)>, String> {
+    return config.split(',').map(str::trim).filter(|entry| !entry.is_empty()).map(|entry| {
+        let (strategy, count) = entry.split_once(':').unwrap_or((entry, "1"));
+        let count = count.parse().map_err(|_| format!("{} is not a number of bots", count))?;
+        return Ok((strategy.parse()?, count));
+    }).collect();
+}
+
+/// starts `count` bots for every strategy, they are called `bot-<strategy>-<n>`
+pub fn spawn(game: &GameServer, sender: &Sender<Message>, bots: &[(Strategy, usize)]) {
+    for (strategy, count) in bots {
+        for index in 1..=*count {
+            let username = format!("{}{}-{}", BOT_PREFIX, strategy.name(), index);
+            tokio::spawn(run(game.clone(), sender.clone(), username, *strategy, BOT_TICK));
+        }
+    }
+}
+
+/// sends one protocol line as `session` and waits for the reply, None once the dispatch loop is gone
+async fn ask(game: &GameServer, sender: &Sender<Message>, session: Uuid, line: String) -> Option<String> {
+    let (response, reply) = flume::bounded::<Message>(1);
+    sender.send(Message { author: session, target: game.server_uuid, data: line.into_bytes(), response: Some(response) }).ok()?;
+
+    let reply = reply.recv_async().await.ok()?;
+    return String::from_utf8(reply.data).ok();
+}
+
+/// logs in as `username` and plays until the rover is kicked, banned or the server shuts down
+pub async fn run(game: GameServer, sender: Sender<Message>, username: String, strategy: Strategy, tick: Duration) {
+    let session = Uuid::new_v4();
+    game.open_bot_session(session).await;
+
+    let login = Command::Login { username: username.clone(), password: Uuid::new_v4().to_string() };
+    let reply = ask(&game, &sender, session, login.to_string()).await;
+    if reply.as_deref() == Some(LOGIN_SUCCESSFUL) {
+        println!("{} started", username);
+
+        let mut bot = Bot::new(strategy);
+        loop {
+            tokio::time::sleep(tick).await;
+
+            // anything that does not parse is `not signed in`, the rover was kicked
+            let position = ask(&game, &sender, session, Command::Position.to_string()).await.and_then(|reply| reply.parse::<Position>().ok());
+            let scan = ask(&game, &sender, session, Command::Scan.to_string()).await.and_then(|reply| reply.parse::<Scan>().ok());
+            let (position, scan) = match (position, scan) {
+                (Some(position), Some(scan)) => (position, scan),
+                _ => break,
+            };
+
+            let command = bot.next(position, &scan);
+            if ask(&game, &sender, session, command.to_string()).await.is_none() {
+                break;
+            }
+        }
+    }
+
+    println!("{} stopped", username);
+    let _ = sender.send(Message { author: session, target: game.server_uuid, data: "disconnect".as_bytes().to_vec(), response: None });
+}
+
+/// what a bot remembers between moves, it only knows what it has scanned itself
+#[derive(Debug)]
+pub struct Bot {
+    strategy: Strategy,
+    known: HashMap<Coord, CellType>,
+    /// cells a forward did not get into, too steep or out of energy
+    blocked: HashSet<Coord>,
+    /// where the last forward started and where it should have ended
+    last_forward: Option<(Coord, Coord)>,
+    leg_length: u32,
+    leg_steps: u32,
+    legs: u32,
+    rng: StdRng,
+}
+
+impl Bot {
+    pub fn new(strategy: Strategy) -> Bot {
+        return Bot {
+            strategy,
+            known: HashMap::new(),
+            blocked: HashSet::new(),
+            last_forward: None,
+            leg_length: SPIRAL_SPACING,
+            leg_steps: 0,
+            legs: 0,
+            rng: StdRng::from_entropy(),
+        };
+    }
+
+    /// the next command for a rover at `position` that has just scanned `scan`
+    pub fn next(&mut self, position: Position, scan: &Scan) -> Command {
+        let here = Coord::new(position.x, position.y);
+        let cells = scan.far.iter().chain(scan.near.iter());
+        for ((dx, dy), cell_type) in Scan::offsets(position.direction).into_iter().zip(cells) {
+            if let Some(coord) = here.offset(dx, dy) {
+                self.known.insert(coord, *cell_type);
+            }
+        }
+
+        if let Some((from, to)) = self.last_forward.take() {
+            if from == here {
+                self.blocked.insert(to);
+            }
+        }
+
+        let (dx, dy) = position.direction.motion();
+        let front = here.offset(dx, dy);
+        let free = front.is_some_and(|front| !self.blocked.contains(&front)) && scan.front() == CellType::Air;
+
+        let command = match self.strategy {
+            Strategy::RandomWalk => self.wander(free),
+            Strategy::Stone => self.dig_stone(here, position.direction, free, scan),
+            Strategy::Spiral => self.spiral(free, scan),
+        };
+
+        if command == Command::Forward {
+            self.last_forward = front.map(|front| (here, front));
+        }
+        return command;
+    }
+
+    fn turn(&mut self) -> Command {
+        return if self.rng.gen_bool(0.5) { Command::TurnLeft } else { Command::TurnRight };
+    }
+
+    fn wander(&mut self, free: bool) -> Command {
+        if free && self.rng.gen_bool(0.8) {
+            return Command::Forward;
+        }
+        return self.turn();
+    }
+
+    fn dig_stone(&mut self, here: Coord, direction: Compass, free: bool, scan: &Scan) -> Command {
+        if scan.front() == CellType::Stone {
+            return Command::Dig;
+        }
+
+        let step = match self.path_to_stone(here) {
+            Some(step) => step,
+            None => return self.wander(free),
+        };
+
+        if step == direction {
+            // rock on the way is dug through, it is worth points too
+            return if scan.front().mineable() { Command::Dig } else { Command::Forward };
+        }
+        return if direction.rotated(true) == step { Command::TurnRight } else { Command::TurnLeft };
+    }
+
+    /// the first direction to drive in to reach a known stone, breadth first over cells that are or might be passable
+    fn path_to_stone(&self, here: Coord) -> Option<Compass> {
+        let mut first_steps: HashMap<Coord, Compass> = HashMap::new();
+        let mut queue = VecDeque::new();
+
+        for direction in Compass::iter() {
+            let (dx, dy) = direction.motion();
+            if let Some(coord) = here.offset(dx, dy) {
+                first_steps.insert(coord, direction);
+                queue.push_back(coord);
+            }
+        }
+
+        while let Some(coord) = queue.pop_front() {
+            let step = first_steps[&coord];
+            match self.known.get(&coord) {
+                Some(CellType::Stone) => return Some(step),
+                Some(CellType::Air | CellType::Rock) | None if !self.blocked.contains(&coord) => {},
+                _ => continue,
+            }
+            // only walk into the unknown close to what has been seen, the search has to end somewhere
+            if first_steps.len() > SEARCH_LIMIT {
+                continue;
+            }
+
+            for direction in Compass::iter() {
+                let (dx, dy) = direction.motion();
+                if let Some(next) = coord.offset(dx, dy) {
+                    if next != here && !first_steps.contains_key(&next) {
+                        first_steps.insert(next, step);
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+
+        return None;
+    }
+
+    fn spiral(&mut self, free: bool, scan: &Scan) -> Command {
+        if self.leg_steps < self.leg_length {
+            if free {
+                self.leg_steps += 1;
+                return Command::Forward;
+            }
+            if scan.front().mineable() {
+                return Command::Dig;
+            }
+        }
+
+        // the side is done or the way is blocked by something that can not be dug, start the next side
+        self.leg_steps = 0;
+        self.legs += 1;
+        if self.legs.is_multiple_of(2) {
+            self.leg_length += SPIRAL_SPACING;
+        }
+        return Command::TurnRight;
+    }
+}
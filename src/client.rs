@@ -1,74 +1,131 @@
-use std::{time::Duration, io::ErrorKind};
-use flume::Sender;
-use tokio::{net::TcpStream, io::Interest};
+use std::sync::Arc;
+use flume::{Receiver, Sender};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
 use uuid::Uuid;
-use crate::Message;
+use crate::limits::{IpSlot, Limiter, Verdict};
+use crate::{metrics::Metrics, Connection, Message};
 
-pub fn handle_client(stream: TcpStream, uuid: Uuid, server_uuid: Uuid, send: Sender::<Message>) {    
+/// reads commands and writes replies at the same time, the task only wakes up when there is something to do.
+/// every command goes through `limiter` first, `slot` is given back when the connection closes
+#[allow(clippy::too_many_arguments)]
+pub fn handle_client(stream: TcpStream, uuid: Uuid, server_uuid: Uuid, send: Sender::<Message>, connection: Connection, client_recv: Receiver::<Message>, metrics: Arc<Metrics>, mut limiter: Limiter, slot: IpSlot) {
     tokio::spawn(async move {
-        let (client_send, client_recv) = flume::unbounded::<Message>();
+        let _slot = slot;
+        // replies are tiny, waiting to fill a packet would only add latency
+        let _ = stream.set_nodelay(true);
+        let (mut reader, mut writer) = stream.into_split();
+        // replies come on their own channel so the limiter can count the commands that still wait for one
+        let (reply_send, reply_recv) = flume::unbounded::<Message>();
+        // the start of a newline terminated command whose end has not arrived yet
+        let mut pending: Vec<u8> = vec![];
+        let mut data = vec![0; 1024];
 
-        loop {
-            let ready = stream.ready(Interest::READABLE | Interest::WRITABLE).await.unwrap();
+        'connection: loop {
+            tokio::select! {
+                read = reader.read(&mut data) => {
+                    let n = match read {
+                        Ok(0) => break,
+                        Ok(n) => n,
+                        Err(e) => {
+                            println!("read error: {}", e.kind());
+                            break;
+                        },
+                    };
+                    metrics.received(n);
 
-            if ready.is_readable() {
-                let mut data = vec![0; 1024];
-
-                match stream.try_read(&mut data) {
-                    Ok(n) => {
-                        if n != 0 {
-                            let data = data[0..n].to_vec();
+                    let mut commands = take_commands(&mut pending, &data[0..n]);
+                    // a line that never ends counts as one that is too long
+                    if limiter.too_long(pending.len()) {
+                        commands.push(std::mem::take(&mut pending));
+                    }
 
-                            let _line = match String::from_utf8(data.clone()) {
-                                Ok(line) => line,
-                                Err(_) => "not utf8".to_owned(),
-                            };
+                    for data in commands {
+                        match limiter.check(&data) {
+                            Verdict::Accept => {},
+                            Verdict::Reject(reply) => {
+                                metrics.rejected();
+                                if writer.write_all(&reply).await.is_err() {
+                                    break 'connection;
+                                }
+                                continue;
+                            },
+                            Verdict::Disconnect(reply) => {
+                                println!("dropped {}: {}", uuid, String::from_utf8_lossy(&reply));
+                                metrics.dropped();
+                                let _ = writer.write_all(&reply).await;
+                                break 'connection;
+                            },
+                        }
 
-                            //println!("read {} bytes, {:X?}, {:?}", data.len(), data, line);
-                            
-                            send.send(Message { author: uuid, target: server_uuid, data, response: Some(client_send.clone()) }).unwrap();
+                        // the server is gone
+                        if send.send(Message { author: uuid, target: server_uuid, data, response: Some(reply_send.clone()) }).is_err() {
+                            break 'connection;
                         }
                     }
-                    Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
-                        //println!("block");
-                    }
-                    Err(e) => {
-                        println!("read error: {}", e.kind());
-                        break;
-                    }
-                }
-            }
-
-            tokio::time::sleep(Duration::from_millis(10)).await;
-
-            if ready.is_writable() {
-                let message = match client_recv.recv_timeout(Duration::from_millis(10)) {
-                    Ok(message) => message,
-                    Err(_) => {
+                },
+                reply = reply_recv.recv_async() => {
+                    // this task holds a sender itself, so the channel never closes
+                    let reply = reply.unwrap();
+                    limiter.replied();
+                    if reply.target != uuid || reply.data.is_empty() {
                         continue;
-                    },
-                };
+                    }
 
-                if message.target != uuid {
-                    continue;
-                }
-                
-                let _line = String::from_utf8(message.data.clone()).unwrap();
-                //println!("response: {:?}", line);
-                
-                match stream.try_write(&message.data) {
-                    Ok(_n) => {
-                        //println!("write {} bytes", n);
+                    if let Err(e) = writer.write_all(&reply.data).await {
+                        println!("write error: {}", e.kind());
+                        break;
                     }
-                    Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                    metrics.sent(reply.data.len());
+                },
+                message = client_recv.recv_async() => {
+                    // the session holds a sender, so the channel stays open until the session is closed
+                    let message = match message {
+                        Ok(message) => message,
+                        Err(_) => break,
+                    };
+                    if message.target != uuid || message.data.is_empty() {
                         continue;
                     }
-                    Err(e) => {
+
+                    if let Err(e) = writer.write_all(&message.data).await {
                         println!("write error: {}", e.kind());
                         break;
                     }
-                }
+                    metrics.sent(message.data.len());
+                },
+                _ = connection.kick.notified() => {
+                    // let the client know why before hanging up
+                    for message in reply_recv.drain().chain(client_recv.drain()).filter(|message| message.target == uuid && !message.data.is_empty()) {
+                        let _ = writer.write_all(&message.data).await;
+                    }
+                    break;
+                },
             }
         }
+
+        let _ = send.send(Message { author: uuid, target: server_uuid, data: "disconnect".as_bytes().to_vec(), response: None });
     });
-}
\ No newline at end of file
+}
+
+/// splits what was read into commands. newline terminated commands can share a read and span several,
+/// a read without any newline that does not continue a command is a whole command on its own
+fn take_commands(pending: &mut Vec<u8>, read: &[u8]) -> Vec<Vec<u8>> {
+    let continues = !pending.is_empty();
+    pending.extend_from_slice(read);
+
+    let end = match pending.iter().rposition(|byte| *byte == b'\n') {
+        Some(end) => end,
+        None if continues => return vec![],
+        None => return vec![std::mem::take(pending)],
+    };
+
+    let rest = pending.split_off(end + 1);
+    let lines = std::mem::replace(pending, rest);
+
+    return lines.split(|byte| *byte == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_vec())
+        .collect();
+}
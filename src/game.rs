@@ -0,0 +1,351 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use flume::Sender;
+use serde_json::Value;
+use tokio::sync::{broadcast, Mutex, Notify, RwLock};
+use uuid::Uuid;
+use crate::bots::BOT_PREFIX;
+use crate::limits::{IpSlots, Limits};
+use crate::live::{self, LiveEvent};
+use crate::metrics::Metrics;
+use crate::planet::{CellType, Coord, Planet};
+use crate::rover::Rover;
+use crate::sessions::{Driver, Session, SessionRegistry};
+use ma_rs_protocol::{server_message, Command, ParseError, BANNED, LOGIN_FAILED, LOGIN_SUCCESSFUL, NOT_SIGNED_IN};
+
+pub const LIVE_CHANNEL_SIZE: usize = 1024;
+/// how often a login tries another spawnpoint when the one it picked was just taken
+const SPAWN_ATTEMPTS: usize = 16;
+/// every rover that is not a bot, saved next to the chunks of the planet
+pub const ROVERS_FILE: &str = "rovers.json";
+
+/// one protocol line on its way between a connection and the dispatch loop
+#[derive(Debug)]
+pub struct Message {
+    pub author: Uuid,
+    pub target: Uuid,
+    pub data: Vec<u8>,
+    pub response: Option<Sender<Message>>,
+}
+
+/// how the server reaches a connected client outside of replies
+#[derive(Debug, Clone)]
+pub struct Connection {
+    pub sender: Sender<Message>,
+    /// notified when the client should be disconnected
+    pub kick: Arc<Notify>,
+}
+
+impl Connection {
+    pub fn new(sender: Sender<Message>) -> Connection {
+        return Connection { sender, kick: Arc::new(Notify::new()) };
+    }
+}
+
+/// the answer to one command, empty for commands that do not answer
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reply(pub String);
+
+impl Reply {
+    pub fn is_empty(&self) -> bool {
+        return self.0.is_empty();
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        return self.0.into_bytes();
+    }
+}
+
+impl Display for Reply {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return f.write_str(&self.0);
+    }
+}
+
+/// the whole game without any networking, the tcp, http and websocket adapters all drive it through `handle_command`
+#[derive(Debug, Clone)]
+pub struct GameServer {
+    pub sessions: SessionRegistry,
+    /// every command reads it at the same time, only replacing the whole planet writes
+    pub planet: Arc<RwLock<Planet>>,
+    pub live_sender: broadcast::Sender<LiveEvent>,
+    /// usernames that are not allowed to log in
+    pub bans: Arc<Mutex<HashSet<String>>>,
+    pub server_uuid: Uuid,
+    pub metrics: Arc<Metrics>,
+    /// what every tcp, websocket and http session may send
+    pub limits: Limits,
+    /// open connections and http sessions per address, limited by `limits.connections_per_ip`
+    pub ip_slots: IpSlots,
+}
+
+impl GameServer {
+    /// has to be called inside the tokio runtime, the sessions get their own task
+    pub fn new(planet: Planet) -> GameServer {
+        let (live_sender, _) = broadcast::channel::<LiveEvent>(LIVE_CHANNEL_SIZE);
+
+        return GameServer {
+            sessions: SessionRegistry::spawn(),
+            planet: Arc::new(RwLock::new(planet)),
+            live_sender,
+            bans: Arc::new(Mutex::new(HashSet::new())),
+            server_uuid: Uuid::new_v4(),
+            metrics: Arc::new(Metrics::default()),
+            limits: Limits::default(),
+            ip_slots: IpSlots::default(),
+        };
+    }
+
+    /// a new client that is not logged in yet
+    pub async fn open_session(&self, session: Uuid, connection: Option<Connection>) {
+        self.sessions.with(move |sessions| sessions.open(Session { uuid: session, connection, bot: false, username: None })).await;
+    }
+
+    /// a session for a bot, it can only log in as bot rovers
+    pub async fn open_bot_session(&self, session: Uuid) {
+        self.sessions.with(move |sessions| sessions.open(Session { uuid: session, connection: None, bot: true, username: None })).await;
+    }
+
+    /// the connection is gone, its rover goes offline and the session is forgotten
+    pub async fn close_session(&self, session: Uuid) {
+        if let Some(username) = self.sessions.with(move |sessions| sessions.close(session)).await {
+            println!("{:?} just logged off", username);
+        }
+    }
+
+    /// runs one line of the rover protocol for `session`, every command gets exactly one reply
+    pub async fn handle_command(&self, session: Uuid, message_string: &str) -> Reply {
+        let GameServer { planet: mars, live_sender, metrics, .. } = self;
+
+        let command = Command::parse(message_string);
+
+        if command == Ok(Command::Disconnect) {
+            if let Some(username) = self.sessions.with(move |sessions| sessions.logout(session)).await {
+                println!("{:?} just logged off", username);
+            }
+            return Reply::default();
+        }
+
+        let rover = match self.sessions.with(move |sessions| sessions.driver(session)).await {
+            Driver::Unknown => return Reply(NOT_SIGNED_IN.to_owned()),
+            Driver::LoggedOut { bot } => return self.login(session, bot, command).await,
+            Driver::Driving(rover) => rover,
+        };
+        let mut rover = rover.lock().await;
+
+        let command = match command {
+            Ok(command) => command,
+            Err(error) => {
+                println!("{}: {}", rover.username, error);
+                return Reply::default();
+            },
+        };
+
+        println!("{}: {}", rover.username, command.name());
+
+        let before = (rover.coord(), rover.rotation);
+        let planet = mars.read().await;
+
+        rover.recharge();
+
+        let reply = match command {
+            Command::Position => rover.position(),
+            Command::Forward => {
+                if let Err(error) = rover.forward(&planet) {
+                    println!("{} can not drive: {}", rover.username, error);
+                }
+                String::new()
+            },
+            Command::TurnLeft => {
+                rover.rotate(false);
+                String::new()
+            },
+            Command::TurnRight => {
+                rover.rotate(true);
+                String::new()
+            },
+            Command::Scan => rover.scan(&planet),
+            Command::ScanHeight => rover.scan_height(&planet),
+            Command::Energy => rover.energy(),
+            Command::Map => rover.map(&planet),
+            Command::Dig => {
+                if let Some((cell_type, points)) = rover.dig(&planet) {
+                    metrics.dig(cell_type, points);
+                }
+                String::new()
+            },
+            // already logged in, and disconnect was handled above
+            Command::Login { .. } | Command::Disconnect => String::new(),
+        };
+
+        planet.load_around(rover.coord());
+
+        live::publish_changes(&planet, live_sender);
+        if before != (rover.coord(), rover.rotation) {
+            let _ = live_sender.send(LiveEvent::rover(&rover));
+        }
+
+        return Reply(reply);
+    }
+
+    /// the first command of a session that does not drive a rover yet
+    async fn login(&self, session: Uuid, bot: bool, command: Result<Command, ParseError>) -> Reply {
+        let (username, password) = match command {
+            Ok(Command::Login { username, password }) => (username, password),
+            Err(ParseError::Arguments("login")) => return Reply(LOGIN_FAILED.to_owned()),
+            _ => return Reply(NOT_SIGNED_IN.to_owned()),
+        };
+
+        println!("login: {:?}", username);
+
+        if self.bans.lock().await.contains(&username) {
+            return Reply(BANNED.to_owned());
+        }
+
+        // bot names are reserved so a bot can always be told apart from a player
+        if username.starts_with(BOT_PREFIX) != bot {
+            return Reply(LOGIN_FAILED.to_owned());
+        }
+
+        let name = username.clone();
+        match self.sessions.with(move |sessions| sessions.rover(&name)).await {
+            // the rover is already driven by someone else
+            Some((_, true)) => return Reply(LOGIN_FAILED.to_owned()),
+            Some((rover, false)) => {
+                let rover = rover.lock().await;
+                if rover.password != password || rover.bot != bot {
+                    return Reply(LOGIN_FAILED.to_owned());
+                }
+            },
+            None => {
+                let spawnpoint = match self.claim_spawn().await {
+                    Some(spawnpoint) => spawnpoint,
+                    None => return Reply(LOGIN_FAILED.to_owned()),
+                };
+
+                let mut rover = Rover::new(username.clone(), password, spawnpoint.x, spawnpoint.y);
+                rover.bot = bot;
+                let name = username.clone();
+                // someone else might have created it in the meantime
+                if !self.sessions.with(move |sessions| sessions.add_rover(name, Arc::new(Mutex::new(rover)))).await {
+                    self.planet.read().await.set_celltype(spawnpoint, CellType::Air).unwrap();
+                    return Reply(LOGIN_FAILED.to_owned());
+                }
+            },
+        }
+
+        let name = username.clone();
+        if !self.sessions.with(move |sessions| sessions.attach(session, &name)).await {
+            return Reply(LOGIN_FAILED.to_owned());
+        }
+
+        println!("{:?} just logged on", username);
+        return Reply(LOGIN_SUCCESSFUL.to_owned());
+    }
+
+    /// a free cell that is now taken by a new rover, other logins might pick the same one at the same time
+    async fn claim_spawn(&self) -> Option<Coord> {
+        let planet = self.planet.read().await;
+        for _ in 0..SPAWN_ATTEMPTS {
+            let spawnpoint = planet.random_spawn(&mut rand::thread_rng())?;
+            if planet.replace(spawnpoint, CellType::Air, CellType::Rover) == Ok(true) {
+                planet.load_around(spawnpoint);
+                return Some(spawnpoint);
+            }
+        }
+        return None;
+    }
+
+    /// a copy of the rover, whether it is online or not
+    pub async fn find_rover(&self, username: &str) -> Option<Rover> {
+        let username = username.to_owned();
+        let (rover, _) = self.sessions.with(move |sessions| sessions.rover(&username)).await?;
+        let rover = rover.lock().await.clone();
+        return Some(rover);
+    }
+
+    /// copies of every known rover and whether it is currently logged in
+    pub async fn all_rovers(&self) -> Vec<(Rover, bool)> {
+        let mut rovers = vec![];
+        for (rover, online) in self.sessions.with(|sessions| sessions.rovers()).await {
+            rovers.push((rover.lock().await.clone(), online));
+        }
+        return rovers;
+    }
+
+    /// tells every connection the server is going away, takes all rovers offline and saves the world to `dir`
+    pub async fn shutdown(&self, dir: &Path) {
+        for session in self.sessions.with(|sessions| sessions.drain()).await {
+            if let Some(connection) = session.connection {
+                let _ = connection.sender.send(Message { author: self.server_uuid, target: session.uuid, data: server_message("server is shutting down").into_bytes(), response: None });
+                connection.kick.notify_one();
+            }
+        }
+
+        match self.save(dir).await {
+            Ok(()) => println!("saved world"),
+            Err(error) => println!("failed to save world: {}", error),
+        }
+    }
+
+    /// writes the planet and every rover to `dir`, the file io runs on a blocking thread.
+    /// bots log in with a new password on every start, so they get new rovers instead of being saved
+    pub async fn save(&self, dir: &Path) -> io::Result<()> {
+        let rovers: Vec<Value> = self.all_rovers().await.iter()
+            .filter(|(rover, _)| !rover.bot)
+            .map(|(rover, _)| rover.to_json())
+            .collect();
+
+        let planet = self.planet.clone().read_owned().await;
+        let dir = dir.to_owned();
+        return tokio::task::spawn_blocking(move || {
+            planet.save(&dir)?;
+            fs::write(dir.join(ROVERS_FILE), Value::Array(rovers).to_string())?;
+            return Ok(());
+        }).await?;
+    }
+
+    /// puts the rovers saved in `dir` back on the planet as offline rovers, returns how many there were.
+    /// a rover whose cell has been taken in the meantime moves to a new spawnpoint
+    pub async fn restore(&self, dir: &Path) -> io::Result<usize> {
+        let json = match fs::read_to_string(dir.join(ROVERS_FILE)) {
+            Ok(json) => json,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(error) => return Err(error),
+        };
+
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("invalid {}", ROVERS_FILE));
+        let saved: Value = serde_json::from_str(&json).map_err(|_| invalid())?;
+        let mut rovers = vec![];
+        for value in saved.as_array().ok_or_else(invalid)? {
+            rovers.push(Rover::from_json(value).ok_or_else(invalid)?);
+        }
+
+        let count = rovers.len();
+        for mut rover in rovers {
+            let planet = self.planet.read().await;
+            // an edited file can put a rover outside the world or onto a taken cell, either way it gets a new spawn point
+            if planet.replace(rover.coord(), CellType::Air, CellType::Rover) != Ok(true) {
+                drop(planet);
+                let spawnpoint = self.claim_spawn().await.ok_or_else(|| io::Error::other("the planet has no room for every rover"))?;
+                rover.x = spawnpoint.x;
+                rover.y = spawnpoint.y;
+                rover.explored.insert(spawnpoint);
+            } else {
+                planet.load_around(rover.coord());
+            }
+
+            let (username, coord) = (rover.username.clone(), rover.coord());
+            // the file was written from the registry, a name can only be in it twice if it was edited by hand
+            if !self.sessions.with(move |sessions| sessions.add_rover(username, Arc::new(Mutex::new(rover)))).await {
+                self.planet.read().await.set_celltype(coord, CellType::Air).map_err(|_| invalid())?;
+            }
+        }
+
+        return Ok(count);
+    }
+}
@@ -0,0 +1,22 @@
+#![allow(clippy::needless_return)]
+
+//! the rover game and its http routers, so it can be driven from tests and other binaries
+
+pub mod planet;
+pub mod rover;
+pub mod live;
+pub mod metrics;
+pub mod game;
+pub mod sessions;
+pub mod client;
+pub mod server;
+pub mod bots;
+pub mod limits;
+pub mod admin;
+pub mod api;
+pub mod maps;
+pub mod play;
+pub mod web;
+
+pub use game::{Connection, GameServer, Message, Reply};
+pub use sessions::Session;
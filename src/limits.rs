@@ -0,0 +1,168 @@
+//! how much one connection may ask of the server, set with `MA_RS_LIMITS` like `rate:20,burst:40,line:256`
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use ma_rs_protocol::{server_message, Command, LINE_TOO_LONG, RATE_LIMITED, TOO_MANY_PENDING};
+
+/// every limit is per connection except `connections_per_ip`, 0 turns a limit off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// commands a connection may send per second on average
+    pub rate: u32,
+    /// commands a connection may send at once before the rate applies
+    pub burst: u32,
+    /// commands that are sent but not answered yet
+    pub pending: usize,
+    /// bytes in one command line
+    pub line: usize,
+    pub connections_per_ip: usize,
+    /// rate limited commands in a row before the connection is dropped
+    pub strikes: u32,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        return Limits { rate: 20, burst: 40, pending: 32, line: 256, connections_per_ip: 32, strikes: 20 };
+    }
+}
+
+impl Limits {
+    /// no limits at all, for tests and benchmarks
+    pub fn none() -> Limits {
+        return Limits { rate: 0, burst: 0, pending: 0, line: 0, connections_per_ip: 0, strikes: 0 };
+    }
+
+    /// a list like `rate:20,burst:40,pending:32,line:256,ip:32,strikes:20`, anything left out keeps its default
+    pub fn parse(config: &str) -> Result<Limits, String> {
+        let mut limits = Limits::default();
+
+        for entry in config.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (name, value) = entry.split_once(':').ok_or(format!("{} needs a value like {}:10", entry, entry))?;
+            let value: u32 = value.parse().map_err(|_| format!("{} is not a number", value))?;
+            match name {
+                "rate" => limits.rate = value,
+                "burst" => limits.burst = value,
+                "pending" => limits.pending = value as usize,
+                "line" => limits.line = value as usize,
+                "ip" => limits.connections_per_ip = value as usize,
+                "strikes" => limits.strikes = value,
+                _ => return Err(format!("unknown limit {}, use rate, burst, pending, line, ip or strikes", name)),
+            }
+        }
+        return Ok(limits);
+    }
+}
+
+/// what happens to one command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Accept,
+    /// the command is dropped and the client gets this instead of a reply
+    Reject(Vec<u8>),
+    /// the client gets this and the connection is closed
+    Disconnect(Vec<u8>),
+}
+
+/// the limits of one connection, it sees every command before the game does
+#[derive(Debug)]
+pub struct Limiter {
+    limits: Limits,
+    tokens: f64,
+    refilled: Instant,
+    pending: usize,
+    strikes: u32,
+}
+
+impl Limiter {
+    pub fn new(limits: Limits) -> Limiter {
+        return Limiter { limits, tokens: limits.burst.max(1) as f64, refilled: Instant::now(), pending: 0, strikes: 0 };
+    }
+
+    /// whether a command that has not been completely read yet is already too long
+    pub fn too_long(&self, length: usize) -> bool {
+        return self.limits.line != 0 && length > self.limits.line;
+    }
+
+    pub fn check(&mut self, line: &[u8]) -> Verdict {
+        if self.too_long(line.len()) {
+            return Verdict::Disconnect(LINE_TOO_LONG.as_bytes().to_vec());
+        }
+        if self.limits.pending != 0 && self.pending >= self.limits.pending {
+            return Verdict::Disconnect(TOO_MANY_PENDING.as_bytes().to_vec());
+        }
+
+        if self.limits.rate != 0 {
+            let now = Instant::now();
+            let burst = self.limits.burst.max(1) as f64;
+            self.tokens = (self.tokens + now.duration_since(self.refilled).as_secs_f64() * self.limits.rate as f64).min(burst);
+            self.refilled = now;
+
+            if self.tokens < 1.0 {
+                self.strikes += 1;
+                if self.limits.strikes != 0 && self.strikes >= self.limits.strikes {
+                    return Verdict::Disconnect(RATE_LIMITED.as_bytes().to_vec());
+                }
+                return Verdict::Reject(rejection(line));
+            }
+            self.tokens -= 1.0;
+        }
+
+        self.strikes = 0;
+        self.pending += 1;
+        return Verdict::Accept;
+    }
+
+    /// the game answered one of the accepted commands
+    pub fn replied(&mut self) {
+        self.pending = self.pending.saturating_sub(1);
+    }
+}
+
+/// commands that do not reply get a message instead, so the next reply the client reads still belongs to its next command
+fn rejection(line: &[u8]) -> Vec<u8> {
+    let replies = std::str::from_utf8(line).ok().and_then(|line| Command::parse(line).ok()).is_none_or(|command| command.replies());
+    if replies {
+        return RATE_LIMITED.as_bytes().to_vec();
+    }
+    return server_message(RATE_LIMITED).into_bytes();
+}
+
+/// open connections per address, shared by everything that accepts connections through `GameServer::ip_slots`
+#[derive(Debug, Clone, Default)]
+pub struct IpSlots {
+    open: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl IpSlots {
+    /// a slot for one more connection from `ip`, None if it already has `limit` of them
+    pub fn take(&self, ip: IpAddr, limit: usize) -> Option<IpSlot> {
+        let mut open = self.open.lock().unwrap();
+        let count = open.entry(ip).or_insert(0);
+        if limit != 0 && *count >= limit {
+            return None;
+        }
+        *count += 1;
+        return Some(IpSlot { slots: self.clone(), ip });
+    }
+}
+
+/// held by a connection for as long as it is open
+#[derive(Debug)]
+pub struct IpSlot {
+    slots: IpSlots,
+    ip: IpAddr,
+}
+
+impl Drop for IpSlot {
+    fn drop(&mut self) {
+        let mut open = self.slots.open.lock().unwrap();
+        if let Some(count) = open.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                open.remove(&self.ip);
+            }
+        }
+    }
+}
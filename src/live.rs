@@ -0,0 +1,102 @@
+use std::sync::Arc;
+use axum::extract::ws::{Message as WsMessage, WebSocket};
+use serde_json::{json, Value};
+use tokio::sync::{broadcast::{self, error::RecvError}, RwLock};
+use crate::planet::{Cell, Planet};
+use crate::rover::{Compass, Rover};
+
+/// something that changed in the world and should be pushed to live viewers
+#[derive(Debug, Clone)]
+pub enum LiveEvent {
+    Cell { x: i32, y: i32, color: [u8; 3] },
+    Rover { username: String, x: i32, y: i32, rotation: Compass },
+    /// a message from the admins
+    Notice { message: String },
+    /// the whole board changed, viewers get it again
+    Reset,
+}
+
+impl LiveEvent {
+    pub fn cell(planet: &Planet, cell: Cell) -> LiveEvent {
+        return LiveEvent::Cell { x: cell.coord.x, y: cell.coord.y, color: planet.cell_color(cell).rgb() };
+    }
+
+    pub fn rover(rover: &Rover) -> LiveEvent {
+        return LiveEvent::Rover { username: rover.username.clone(), x: rover.x, y: rover.y, rotation: rover.rotation };
+    }
+
+    pub fn to_json(&self) -> Value {
+        return match self {
+            LiveEvent::Cell { x, y, color } => json!({
+                "type": "cell",
+                "x": x,
+                "y": y,
+                "color": color,
+            }),
+            LiveEvent::Rover { username, x, y, rotation } => json!({
+                "type": "rover",
+                "username": username,
+                "x": x,
+                "y": y,
+                "rotation": format!("{:?}", rotation),
+            }),
+            LiveEvent::Notice { message } => json!({
+                "type": "notice",
+                "message": message,
+            }),
+            LiveEvent::Reset => json!({
+                "type": "reset",
+            }),
+        };
+    }
+}
+
+/// sends the cells that changed since the last call to the live viewers
+pub fn publish_changes(planet: &Planet, sender: &broadcast::Sender<LiveEvent>) {
+    // send errors only mean nobody is watching
+    for cell in planet.take_changes() {
+        let _ = sender.send(LiveEvent::cell(planet, cell));
+    }
+}
+
+async fn send_full(socket: &mut WebSocket, planet: &Arc<RwLock<Planet>>) -> Result<(), axum::Error> {
+    let planet = planet.read().await;
+    let response = json!({
+        "type": "full",
+        "board": planet.color_buffer(),
+        "planet_size": planet.size,
+    });
+    drop(planet);
+
+    return socket.send(WsMessage::Text(response.to_string())).await;
+}
+
+/// sends the whole board once and then every event until the viewer goes away,
+/// a viewer that falls behind gets the whole board again instead of the missed events
+pub async fn stream(mut socket: WebSocket, planet: Arc<RwLock<Planet>>, mut events: broadcast::Receiver<LiveEvent>) {
+    if send_full(&mut socket, &planet).await.is_err() {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let result = match event {
+                    Ok(LiveEvent::Reset) | Err(RecvError::Lagged(_)) => send_full(&mut socket, &planet).await,
+                    Ok(event) => socket.send(WsMessage::Text(event.to_json().to_string())).await,
+                    Err(RecvError::Closed) => return,
+                };
+
+                if result.is_err() {
+                    return;
+                }
+            }
+            message = socket.recv() => {
+                match message {
+                    Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => return,
+                    Some(Ok(_)) => {},
+                }
+            }
+        }
+    }
+}
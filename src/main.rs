@@ -1,45 +1,77 @@
-mod planet;
-mod client;
-mod rover;
+#![allow(clippy::needless_return)]
 
-use std::collections::VecDeque;
+use std::path::Path;
+use std::time::Duration;
 use std::{net::SocketAddr, sync::Arc};
-use flume::Sender;
-use planet::{Planet, CellType};
-use planet::Cell;
-use rand::Rng;
-use rover::Rover;
-use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
+use ma_rs::planet::{Planet, CellType, Coord};
+use ma_rs::limits::Limits;
+use ma_rs::{admin, api, bots, server, web, GameServer, Message};
 use tokio::net::TcpListener;
-use client::handle_client;
-use tokio::sync::Mutex;
-use uuid::Uuid;
-use axum::{routing::get, Router};
-use tower_http::services::ServeDir;
+use tokio::signal;
+use axum_server::Handle;
 
 static PLANET_SIZE: u32 = 100;
+static WORLD_DIR: &str = "world";
+static AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+static ADMIN_TOKEN_VARIABLE: &str = "MA_RS_ADMIN_TOKEN";
+/// which bots to start, like `random:2,stone:1,spiral:1`
+static BOTS_VARIABLE: &str = "MA_RS_BOTS";
+/// what one connection may send, like `rate:20,burst:40,pending:32,line:256,ip:32,strikes:20`
+static LIMITS_VARIABLE: &str = "MA_RS_LIMITS";
+/// how long open http requests get to finish when the server shuts down
+static SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+/// how long kicked connections get to write their last message before the process exits
+static SHUTDOWN_FLUSH: Duration = Duration::from_millis(200);
 
 #[tokio::main]
 async fn main() {
-    let mars = Planet::new(PLANET_SIZE);
+    let mars = match Planet::load(Path::new(WORLD_DIR)) {
+        Ok(planet) => {
+            println!("loaded world with seed {}", planet.seed());
+            planet
+        },
+        Err(_) => Planet::new(PLANET_SIZE),
+    };
     
-    let cells =  mars.cells();
-    let air_cells: Vec<&Cell> = cells.iter().filter(|a| a.cell_type == CellType::Air).collect();
-    let rock_cells: Vec<&Cell> = cells.iter().filter(|a| a.cell_type == CellType::Rock).collect();
-    let stone_cells: Vec<&Cell> = cells.iter().filter(|a| a.cell_type == CellType::Stone).collect();
-    let water_cells: Vec<&Cell> = cells.iter().filter(|a| a.cell_type == CellType::Water).collect();
-    let bedrock_cells: Vec<&Cell> = cells.iter().filter(|a| a.cell_type == CellType::Bedrock).collect();
-
     println!("--- planet stats ---");
-    println!("air: {} rock: {} stone: {} water: {} bedrock: {}", air_cells.len(), rock_cells.len(), stone_cells.len(), water_cells.len(),  bedrock_cells.len());
+    let counts = mars.cell_counts();
+    let count = |cell_type| counts.get(&cell_type).copied().unwrap_or(0);
+    println!("air: {} rock: {} stone: {} water: {} bedrock: {}", count(CellType::Air), count(CellType::Rock), count(CellType::Stone), count(CellType::Water), count(CellType::Bedrock));
 
     //fs::write("map.txt", mars.print_ascii()).unwrap();
 
-    let clients = Arc::new(Mutex::new(vec![]));
-    let server = TcpListener::bind("0.0.0.0:6969").await.unwrap();
+    let mut game = GameServer::new(mars);
+    if let Ok(config) = std::env::var(LIMITS_VARIABLE) {
+        match Limits::parse(&config) {
+            Ok(limits) => game.limits = limits,
+            Err(error) => {
+                println!("{}: {}", LIMITS_VARIABLE, error);
+                std::process::exit(1);
+            },
+        }
+    }
+    match game.restore(Path::new(WORLD_DIR)).await {
+        Ok(0) => {},
+        Ok(count) => println!("restored {} rovers", count),
+        Err(error) => {
+            println!("failed to restore rovers: {}", error);
+            std::process::exit(1);
+        },
+    }
+
+    let listener = match TcpListener::bind("0.0.0.0:6969").await {
+        Ok(listener) => listener,
+        Err(error) => {
+            println!("failed to listen on 0.0.0.0:6969: {}", error);
+            std::process::exit(1);
+        },
+    };
 
-    let server_uuid = Uuid::new_v4();
-    println!("server uuid: {}", server_uuid);
+    // false until the web server listens and again once shutdown has started
+    let ready = Arc::new(AtomicBool::new(false));
+
+    println!("server uuid: {}", game.server_uuid);
 
     /*
     let mut img: RgbImage = ImageBuffer::new(PLANET_SIZE, PLANET_SIZE);
@@ -49,181 +81,113 @@ async fn main() {
 
     let message_channel = flume::unbounded::<Message>();
     
-    let client_pusher = clients.clone();
     let (sender, _) = message_channel.clone();
-    tokio::spawn(async move {
-        loop {
-            let (stream, _sock_addr) = server.accept().await.unwrap();
-
-            let client_uuid = Uuid::new_v4();
-            client_pusher.lock().await.push(Client { uuid: client_uuid, rover: None });
-            handle_client(stream, client_uuid, server_uuid, sender.clone());
+    let api_sender = sender.clone();
+    let web_sender = sender.clone();
+    let bots_sender = sender.clone();
+    let accept_task = tokio::spawn(server::accept(game.clone(), listener, sender));
+
+    if let Ok(config) = std::env::var(BOTS_VARIABLE) {
+        match bots::parse_config(&config) {
+            Ok(config) => bots::spawn(&game, &bots_sender, &config),
+            Err(error) => {
+                println!("{}: {}", BOTS_VARIABLE, error);
+                std::process::exit(1);
+            },
         }
-    });
+    }
 
-    let mars: Arc<Mutex<Planet>> = Arc::new(Mutex::new(mars));
-    let mars_web = mars.clone();
-    //webclient
+    let console_state = game.clone();
     tokio::spawn(async move {
-        let app = Router::new()
-            .nest_service("/", ServeDir::new("web"))
-            .route("/planet", get(|| async {
-                let mars = mars_web.clone();
-                let planet =  mars.lock().await;
-                let response = json!({
-                    "board": planet.color_buffer(),
-                    "planet_size": planet.size,
-                });
-                drop(mars_web);
-                
-                serde_json::to_string(&response).unwrap()
-             }));
-        
-        let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
-        println!("listening on {}", addr);
-
-        axum_server::bind(addr)
-            .serve(app.into_make_service()).await.unwrap();
+        admin::console(console_state).await;
     });
 
-    let mut offline_rovers: Vec<Rover> = vec![];
-
-    
-    let (_, receiver) = message_channel;
-    loop {
-        let message = match receiver.recv() {
-            Ok(message) => message,
-            Err(_error) => {
-                //println!("hejsan {:?}", _error);
-                continue;
-            },
-        };
-        
-        //println!("message: {:#?}", message);
-        if message.target != server_uuid {
-            continue;
-        }
-
-        let message_string = match String::from_utf8(message.data) {
-            Ok(message) => message,
-            Err(_) => {
-                println!("this is not utf8");
-                continue
-            },
-        };
+    let game_autosave = game.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(AUTOSAVE_INTERVAL).await;
 
-        //println!("{:?}", message_string);
+            let mut positions: Vec<Coord> = vec![];
+            for (rover, online) in game_autosave.sessions.with(|sessions| sessions.rovers()).await {
+                if online {
+                    positions.push(rover.lock().await.coord());
+                }
+            }
 
-        let mut args: VecDeque<&str> = message_string.split(" ").collect();
-        let command = args.pop_front().unwrap();
+            let planet = game_autosave.planet.clone().read_owned().await;
+            let _ = tokio::task::spawn_blocking(move || planet.unload_distant(&positions)).await;
 
-        let index = get_client_index(&clients, message.author).await.unwrap();
-        let mut clients_mutex = clients.lock().await;
-        let client = clients_mutex.get_mut(index).unwrap();
-        
-        if client.rover.is_none() {
-            if command != "login" {
-                message.response.unwrap().send(Message { author: server_uuid, target: client.uuid, data: "not signed in".as_bytes().to_vec(), response: None }).unwrap();
-                continue;
+            if let Err(error) = game_autosave.save(Path::new(WORLD_DIR)).await {
+                println!("failed to save world: {}", error);
             }
+        }
+    });
 
-            println!("login: {:?}", args);
+    let web_handle = Handle::new();
+    let web_server_handle = web_handle.clone();
+    let web_game = game.clone();
+    let web_ready = ready.clone();
 
-            if args.len() != 2 {
-                message.response.unwrap().send(Message { author: server_uuid, target: client.uuid, data: "login failed".as_bytes().to_vec(), response: None }).unwrap();
-                continue;
-            }
+    let web_task = tokio::spawn(async move {
+        let app = web::router(web_game.clone(), web_sender, web_ready)
+            .merge(api::router(api_sender, web_game.clone()));
 
-            let mut rover_index = None;
-            for (index, r) in offline_rovers.iter().enumerate() {
-                if args[0] == r.username {
-                    rover_index = Some(index);
-                    break;
-                }
-            }
+        // the admin api only exists when a token is configured
+        let app = match std::env::var(ADMIN_TOKEN_VARIABLE) {
+            Ok(token) if !token.is_empty() => app.merge(admin::router(web_game, token)),
+            _ => {
+                println!("{} is not set, the admin api is disabled", ADMIN_TOKEN_VARIABLE);
+                app
+            },
+        };
 
-            println!("{:?}", rover_index);
-
-            match rover_index {
-                Some(rover_index) => {
-                    if offline_rovers[rover_index].password != args[1] {
-                        message.response.unwrap().send(Message { author: server_uuid, target: client.uuid, data: "login failed".as_bytes().to_vec(), response: None }).unwrap();
-                        continue;
-                    }
-                    let rover = offline_rovers.remove(rover_index);
-
-                    client.rover = Some(rover);
-                },
-                None => {
-                    let cells =  mars.lock().await.cells();
-                    let empty_spots: Vec<&Cell> = cells.iter().filter(|a| a.cell_type == CellType::Air).collect();
-        
-                    let mut rng = rand::thread_rng();
-                    let spawnpoint = empty_spots.get(rng.gen_range(0..empty_spots.len())).unwrap();
-        
-                    client.rover = Some(Rover::new(args[0].to_owned(), args[1].to_owned(), spawnpoint.x, spawnpoint.y, mars.clone()));
-                },
-            }
+        let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
+        println!("listening on {}", addr);
 
-            println!("{:?} just logged on", args[0]);
-            message.response.unwrap().send(Message { author: server_uuid, target: client.uuid, data: "login successful".as_bytes().to_vec(), response: None }).unwrap();
-            continue;
+        if let Err(error) = axum_server::bind(addr).handle(web_server_handle).serve(app.into_make_service_with_connect_info::<SocketAddr>()).await {
+            println!("failed to serve http on {}: {}", addr, error);
+            std::process::exit(1);
         }
-        let rover = client.rover.as_mut().unwrap();
+    });
 
-        println!("{}: {} {:?}", rover.username, command, args);
+    let ready_listening = ready.clone();
+    let listening_handle = web_handle.clone();
+    tokio::spawn(async move {
+        if listening_handle.listening().await.is_some() {
+            ready_listening.store(true, Ordering::Relaxed);
+        }
+    });
 
-        let mut planet = mars.lock().await;
-        planet.set_celltype(rover.x, rover.y, CellType::Air);
-        drop(planet);
+    let (_, receiver) = message_channel;
+    server::dispatch(&game, receiver, shutdown_signal()).await;
 
-        match command {
-            "position" => {
-                let position = rover.position();
-                message.response.unwrap().send(Message { author: server_uuid, target: client.uuid, data: position.as_bytes().to_vec(), response: None }).unwrap();
-            },
-            "forward" => rover.forward().await,
-            "turnleft" => rover.rotate(false).await,
-            "turnright" => rover.rotate(true).await,
-            "scan" => {
-                let scan: String = rover.scan().await;
-                message.response.unwrap().send(Message { author: server_uuid, target: client.uuid, data: scan.as_bytes().to_vec(), response: None }).unwrap();
-            }
-            "dig" => rover.dig().await,
-            "disconnect" => {
-                println!("{:?} just logged off", rover.username);
-                offline_rovers.push(rover.clone());
-            }
-            _ => {
-                println!("unknown command: {:?} {:?}", command, args);
-            }
-        }
+    println!("shutting down");
+    ready.store(false, Ordering::Relaxed);
+    accept_task.abort();
 
-        let mut planet = mars.lock().await;
-        planet.set_celltype(rover.x, rover.y, CellType::Rover);
-    }
-}
+    game.shutdown(Path::new(WORLD_DIR)).await;
+    web_handle.graceful_shutdown(Some(SHUTDOWN_GRACE));
+    let _ = web_task.await;
+    tokio::time::sleep(SHUTDOWN_FLUSH).await;
 
-async fn get_client_index(clients: &Arc<Mutex<Vec<Client>>>, uuid: Uuid) -> Option<usize> {
-    let clients_aa = clients.lock().await;
-    for (index, client) in clients_aa.iter().enumerate() {
-        if client.uuid == uuid {
-            return Some(index);
-        }
-    }
-    return None;
+    println!("bye");
+    // the stdin console would otherwise keep the runtime alive
+    std::process::exit(0);
 }
 
-#[derive(Debug)]
-pub struct Message {
-    author: Uuid,
-    target: Uuid,
-    data: Vec<u8>,
-    response: Option<Sender<Message>>,
-}
+/// resolves on ctrl-c or SIGTERM
+async fn shutdown_signal() {
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            },
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
 
-#[derive(Debug)]
-pub struct Client {
-    uuid: Uuid,
-    rover: Option<Rover>,
+    tokio::select! {
+        _ = signal::ctrl_c() => {},
+        _ = terminate => {},
+    }
 }
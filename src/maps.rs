@@ -0,0 +1,130 @@
+use std::{collections::HashMap, io::Cursor, sync::Arc};
+use axum::{http::{header, HeaderMap, StatusCode}, response::{IntoResponse, Response}, Json};
+use image::{imageops::{self, FilterType}, ImageOutputFormat, RgbImage};
+use serde_json::json;
+use tokio::sync::RwLock;
+use crate::planet::{Coord, Planet};
+
+pub const MAX_SCALE: u32 = 16;
+
+/// cells covered by one tile at zoom level 0, every zoom level halves it
+pub const TILE_CELLS: u32 = 256;
+pub const MAX_ZOOM: u32 = 5;
+
+/// the etag for a rendering of the planet, `variant` tells renderings of the same map apart
+pub fn etag(planet: &Planet, variant: &str) -> String {
+    return format!("\"{:x}-{:x}-{}\"", planet.seed(), planet.version(), variant);
+}
+
+/// true when the client already has the rendering with this etag
+pub fn not_modified(headers: &HeaderMap, etag: &str) -> bool {
+    return match headers.get(header::IF_NONE_MATCH).and_then(|value| value.to_str().ok()) {
+        Some(value) => value.split(',').any(|tag| tag.trim() == etag || tag.trim() == "*"),
+        None => false,
+    };
+}
+
+pub fn render_png(planet: &Planet, scale: u32) -> Vec<u8> {
+    let image = RgbImage::from_raw(planet.size, planet.size, planet.color_buffer()).unwrap();
+    let image = match scale {
+        1 => image,
+        scale => imageops::resize(&image, planet.size * scale, planet.size * scale, FilterType::Nearest),
+    };
+
+    let mut png = Cursor::new(vec![]);
+    image.write_to(&mut png, ImageOutputFormat::Png).unwrap();
+    return png.into_inner();
+}
+
+/// GET /planet.png?scale=n
+pub async fn planet_png(planet: Arc<RwLock<Planet>>, params: HashMap<String, String>, headers: HeaderMap) -> Response {
+    let scale = match params.get("scale").map(|scale| scale.parse::<u32>()) {
+        Some(Ok(scale)) if (1..=MAX_SCALE).contains(&scale) => scale,
+        Some(_) => return (StatusCode::BAD_REQUEST, format!("scale has to be between 1 and {}", MAX_SCALE)).into_response(),
+        None => 1,
+    };
+
+    let planet = planet.read().await;
+    let etag = etag(&planet, &format!("png{}", scale));
+    if not_modified(&headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+
+    let png = render_png(&planet, scale);
+    drop(planet);
+
+    return ([(header::CONTENT_TYPE, "image/png".to_owned()), (header::ETAG, etag)], png).into_response();
+}
+
+/// GET /planet.bin, one `CellType` byte per cell row by row, the side length is in `x-planet-size`
+pub async fn planet_bin(planet: Arc<RwLock<Planet>>, headers: HeaderMap) -> Response {
+    let planet = planet.read().await;
+    let etag = etag(&planet, "bin");
+    if not_modified(&headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+
+    let size = planet.size.to_string();
+    let buffer = planet.cell_type_buffer();
+    drop(planet);
+
+    return ([
+        (header::CONTENT_TYPE, "application/octet-stream".to_owned()),
+        (header::ETAG, etag),
+        (header::HeaderName::from_static("x-planet-size"), size),
+    ], buffer).into_response();
+}
+
+/// GET /tiles/z/x/y.png, a square of `TILE_CELLS >> z` cells drawn at `2^z` pixels per cell
+pub async fn tile(planet: Arc<RwLock<Planet>>, (z, x, y): (u32, i32, String), headers: HeaderMap) -> Response {
+    let y = match y.strip_suffix(".png").map(|y| y.parse::<i32>()) {
+        Some(Ok(y)) => y,
+        _ => return (StatusCode::NOT_FOUND, "tiles are named like 0/0/0.png").into_response(),
+    };
+
+    if z > MAX_ZOOM {
+        return (StatusCode::NOT_FOUND, format!("zoom has to be at most {}", MAX_ZOOM)).into_response();
+    }
+
+    let cells = TILE_CELLS >> z;
+    let origin = match (x.checked_mul(cells as i32), y.checked_mul(cells as i32)) {
+        (Some(tile_x), Some(tile_y)) if Coord::new(tile_x, tile_y).in_bounds() => Coord::new(tile_x, tile_y),
+        _ => return (StatusCode::NOT_FOUND, "tile is outside the world").into_response(),
+    };
+
+    let planet = planet.read().await;
+    let etag = etag(&planet, &format!("tile{}", z));
+    if not_modified(&headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+
+    let mut buffer = Vec::with_capacity((cells * cells * 3) as usize);
+    for cell in planet.cells_in(origin, cells, cells) {
+        buffer.extend_from_slice(&planet.cell_color(cell).rgb());
+    }
+    drop(planet);
+
+    let image = RgbImage::from_raw(cells, cells, buffer).unwrap();
+    let image = imageops::resize(&image, TILE_CELLS, TILE_CELLS, FilterType::Nearest);
+
+    let mut png = Cursor::new(vec![]);
+    image.write_to(&mut png, ImageOutputFormat::Png).unwrap();
+
+    return ([(header::CONTENT_TYPE, "image/png".to_owned()), (header::ETAG, etag)], png.into_inner()).into_response();
+}
+
+/// GET /cell?x=&y=, what is at a single cell
+pub async fn cell(planet: Arc<RwLock<Planet>>, params: HashMap<String, String>) -> Response {
+    let coord = match (params.get("x").map(|x| x.parse()), params.get("y").map(|y| y.parse())) {
+        (Some(Ok(x)), Some(Ok(y))) => Coord::new(x, y),
+        _ => return (StatusCode::BAD_REQUEST, "x and y are required").into_response(),
+    };
+
+    let planet = planet.read().await;
+    return Json(json!({
+        "x": coord.x,
+        "y": coord.y,
+        "cell_type": format!("{:?}", planet.get_cell_type(coord)),
+        "height": planet.height(coord),
+    })).into_response();
+}
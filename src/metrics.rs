@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex as StdMutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use strum::IntoEnumIterator;
+use crate::planet::{CellTrait, CellType};
+use crate::GameServer;
+use ma_rs_protocol::Command;
+
+/// upper bounds of the command latency histogram in seconds
+const LATENCY_BUCKETS: [f64; 9] = [0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0];
+
+/// counters updated by the dispatch loop and the connections, rendered in the prometheus text format
+#[derive(Debug, Default)]
+pub struct Metrics {
+    commands: StdMutex<BTreeMap<&'static str, u64>>,
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    latency_count: AtomicU64,
+    latency_sum_micros: AtomicU64,
+    /// indexed by `CellType as usize`
+    digs: [AtomicU64; 6],
+    points: AtomicU64,
+    connections: AtomicU64,
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    rejected: AtomicU64,
+    dropped: AtomicU64,
+    workers: AtomicU64,
+    /// messages handed to a session worker that it has not started on yet
+    queued: AtomicU64,
+}
+
+impl Metrics {
+    /// counts one processed command and how long it took,
+    /// anything that is not a command is counted as `unknown` so clients can not grow the label set
+    pub fn command(&self, line: &str, latency: Duration) {
+        let name = line.split(' ').next().unwrap_or("");
+        let name = Command::NAMES.iter().find(|command| **command == name).copied().unwrap_or("unknown");
+        *self.commands.lock().unwrap().entry(name).or_insert(0) += 1;
+
+        let seconds = latency.as_secs_f64();
+        for (bucket, bound) in self.latency_buckets.iter().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.latency_count.fetch_add(1, Ordering::Relaxed);
+        self.latency_sum_micros.fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// how many commands the dispatch loop has finished
+    pub fn processed(&self) -> u64 {
+        return self.latency_count.load(Ordering::Relaxed);
+    }
+
+    pub fn dig(&self, cell_type: CellType, points: u32) {
+        self.digs[cell_type as usize].fetch_add(1, Ordering::Relaxed);
+        self.points.fetch_add(points as u64, Ordering::Relaxed);
+    }
+
+    pub fn connection(&self) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn received(&self, bytes: usize) {
+        self.bytes_received.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// a session worker started or stopped, see `server::dispatch`
+    pub fn worker(&self, started: bool) {
+        if started {
+            self.workers.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.workers.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// how many session workers are running
+    pub fn workers(&self) -> u64 {
+        return self.workers.load(Ordering::Relaxed);
+    }
+
+    /// a message was handed to a session worker or taken up by it
+    pub fn queued(&self, handed: bool) {
+        if handed {
+            self.queued.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// a command that was over the rate limit
+    pub fn rejected(&self) {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// a connection that was closed or refused because it broke a limit
+    pub fn dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+}
+
+/// GET /metrics, the counters plus gauges read from the game state, `channel_depth` is the number of messages the dispatch loop has not seen yet
+pub async fn render(state: &GameServer, channel_depth: usize) -> String {
+    let metrics = &state.metrics;
+
+    let (connected, online, offline) = state.sessions.with(|sessions| (sessions.sessions().count(), sessions.online(), sessions.offline())).await;
+
+    let mut out = String::new();
+
+    header(&mut out, "ma_rs_clients", "gauge", "connected clients, logged in or not");
+    writeln!(out, "ma_rs_clients {}", connected).unwrap();
+
+    header(&mut out, "ma_rs_rovers", "gauge", "known rovers");
+    writeln!(out, "ma_rs_rovers{{state=\"online\"}} {}", online).unwrap();
+    writeln!(out, "ma_rs_rovers{{state=\"offline\"}} {}", offline).unwrap();
+
+    header(&mut out, "ma_rs_message_channel_depth", "gauge", "messages waiting for the dispatch loop or a session worker");
+    writeln!(out, "ma_rs_message_channel_depth {}", channel_depth as u64 + metrics.queued.load(Ordering::Relaxed)).unwrap();
+
+    header(&mut out, "ma_rs_session_workers", "gauge", "tasks running the commands of one session each");
+    writeln!(out, "ma_rs_session_workers {}", metrics.workers()).unwrap();
+
+    header(&mut out, "ma_rs_commands_total", "counter", "processed commands by type");
+    for (command, count) in metrics.commands.lock().unwrap().iter() {
+        writeln!(out, "ma_rs_commands_total{{command=\"{}\"}} {}", command, count).unwrap();
+    }
+
+    header(&mut out, "ma_rs_command_latency_seconds", "histogram", "time spent handling a command");
+    for (bucket, bound) in metrics.latency_buckets.iter().zip(LATENCY_BUCKETS) {
+        writeln!(out, "ma_rs_command_latency_seconds_bucket{{le=\"{}\"}} {}", bound, bucket.load(Ordering::Relaxed)).unwrap();
+    }
+    let count = metrics.latency_count.load(Ordering::Relaxed);
+    writeln!(out, "ma_rs_command_latency_seconds_bucket{{le=\"+Inf\"}} {}", count).unwrap();
+    writeln!(out, "ma_rs_command_latency_seconds_sum {}", metrics.latency_sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0).unwrap();
+    writeln!(out, "ma_rs_command_latency_seconds_count {}", count).unwrap();
+
+    header(&mut out, "ma_rs_digs_total", "counter", "cells dug out by type");
+    for cell_type in CellType::iter().filter(|cell_type| cell_type.mineable()) {
+        writeln!(out, "ma_rs_digs_total{{cell_type=\"{:?}\"}} {}", cell_type, metrics.digs[cell_type as usize].load(Ordering::Relaxed)).unwrap();
+    }
+
+    header(&mut out, "ma_rs_points_total", "counter", "points awarded to all rovers");
+    writeln!(out, "ma_rs_points_total {}", metrics.points.load(Ordering::Relaxed)).unwrap();
+
+    header(&mut out, "ma_rs_tcp_connections_total", "counter", "accepted tcp connections");
+    writeln!(out, "ma_rs_tcp_connections_total {}", metrics.connections.load(Ordering::Relaxed)).unwrap();
+
+    header(&mut out, "ma_rs_tcp_received_bytes_total", "counter", "bytes read from tcp clients");
+    writeln!(out, "ma_rs_tcp_received_bytes_total {}", metrics.bytes_received.load(Ordering::Relaxed)).unwrap();
+
+    header(&mut out, "ma_rs_tcp_sent_bytes_total", "counter", "bytes written to tcp clients");
+    writeln!(out, "ma_rs_tcp_sent_bytes_total {}", metrics.bytes_sent.load(Ordering::Relaxed)).unwrap();
+
+    header(&mut out, "ma_rs_rejected_commands_total", "counter", "commands dropped by the rate limit");
+    writeln!(out, "ma_rs_rejected_commands_total {}", metrics.rejected.load(Ordering::Relaxed)).unwrap();
+
+    header(&mut out, "ma_rs_dropped_connections_total", "counter", "connections closed or refused for breaking a limit");
+    writeln!(out, "ma_rs_dropped_connections_total {}", metrics.dropped.load(Ordering::Relaxed)).unwrap();
+
+    return out;
+}
@@ -1,81 +1,314 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
-use std::fmt::Write;
+use std::fs;
+use std::io;
 use std::io::Write as fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
 
-use bracket_noise::prelude::FastNoise;
+use bracket_noise::prelude::{FastNoise, NoiseType};
 use rand::Rng;
-use strum::EnumIter;
 
-#[derive(Debug)]
+pub use ma_rs_protocol::CellType;
+
+pub const CHUNK_SIZE: i32 = 32;
+
+/// distance in chunks around a rover that is kept generated in memory
+pub const LOAD_RADIUS: i32 = 2;
+
+pub const MAX_HEIGHT: u8 = 40;
+
+/// cells further than this from 0,0 in any axis read as bedrock and cannot be written
+pub const WORLD_LIMIT: i32 = 1 << 24;
+
+const FOG_COLOR: CellColor = CellColor { r: 50, g: 50, b: 50 };
+
+const SCATTERNESS: u32 = 4;
+const CELL_TYPES: [CellType; 4] = [CellType::Air, CellType::Rock, CellType::Stone, CellType::Bedrock];
+
+/// every method that changes cells takes `&self`, so rovers anywhere on the planet can act at the same time
 pub struct Planet {
-    cells: Vec<Cell>,
+    seed: u64,
+    noise: FastNoise,
+    height_noise: FastNoise,
+    /// only locked for writing while chunks are added or dropped, cells are atomics that change in place
+    chunks: RwLock<HashMap<ChunkPos, Chunk>>,
+    spawn_index: Mutex<SpawnIndex>,
+    /// cells changed since the last `take_changes`, with the type they had before
+    changes: Mutex<HashMap<Coord, CellType>>,
+    /// bumped on every change, starts at a random number so a new planet never repeats the versions of the one it replaced
+    version: AtomicU64,
+    /// every cell has height 0, for hand drawn maps
+    flat: bool,
+    /// the region starting at 0,0 that is drawn on the web map and used for spawning
     pub size: u32
 }
 
 impl Planet {
     pub fn new(size: u32) -> Planet {
-        let mut cells = vec![];
+        return Planet::with_seed(size, rand::random());
+    }
 
-        let mut rng = rand::thread_rng();
+    pub fn with_seed(size: u32, seed: u64) -> Planet {
+        let mut planet = Planet::unindexed(size, seed);
+        planet.rebuild_spawn_index();
+        return planet;
+    }
 
-        let scatterness = 4;
+    /// a planet whose spawn index is still empty, for constructors that change cells first and rebuild it once
+    fn unindexed(size: u32, seed: u64) -> Planet {
+        let mut noise = FastNoise::new();
+        noise.set_seed(seed);
 
-        let mut cell_types = vec![];
-        cell_types.push(CellType::Air);
-        cell_types.push(CellType::Rock);
-        //cell_types.push(CellType::Water);
-        cell_types.push(CellType::Stone);
-        cell_types.push(CellType::Bedrock);
+        let mut height_noise = FastNoise::new();
+        height_noise.set_seed(seed.wrapping_add(1));
+        height_noise.set_noise_type(NoiseType::SimplexFractal);
+        height_noise.set_fractal_octaves(3);
 
-        let mut noise = FastNoise::new();
-        noise.set_seed(rng.gen_range(0..1000));
-
-        for y in 0..size {
-            for x in 0..size {
-                let cell_types = cell_types.iter();
-                let length = cell_types.len().pow(scatterness);
-                let index = length - rng.gen_range(0..length);
-
-                let mut cell_type = CellType::Air;
-
-                let noise_value = noise.get_noise(x as f32 / 35.0, y as f32 / 35.0);
-
-                if noise_value > 0.5 {
-                    cell_type = CellType::Water;
-                } else {
-                    if noise_value < 0.0 {
-                        for (i, ct) in cell_types.clone().enumerate() {
-                            let number = cell_types.len() - (i + 1);
-        
-                            if index > number.pow(scatterness) {
-                                cell_type = ct.clone();
-                                break;
+        return Planet {
+            seed,
+            noise,
+            height_noise,
+            chunks: RwLock::new(HashMap::new()),
+            spawn_index: Mutex::new(SpawnIndex::new(size, vec![0; (size as usize * size as usize).div_ceil(64)], HashMap::new())),
+            changes: Mutex::new(HashMap::new()),
+            version: AtomicU64::new(rand::random()),
+            flat: false,
+            size
+        };
+    }
+
+    /// a flat planet whose `size` region is the map drawn like `print_ascii`, one row per line,
+    /// the rest of the world is generated from the seed. None when the map is not square or has unknown cells
+    pub fn from_ascii(seed: u64, map: &str) -> Option<Planet> {
+        let rows: Vec<&str> = map.lines().collect();
+        let size = rows.len();
+        if rows.iter().any(|row| row.chars().count() != size) {
+            return None;
+        }
+
+        let mut planet = Planet::unindexed(size as u32, seed);
+        planet.flat = true;
+        for (y, row) in rows.iter().enumerate() {
+            for (x, symbol) in row.chars().enumerate() {
+                let (coord, cell_type) = (Coord::new(x as i32, y as i32), CellType::from_char(symbol)?);
+                if !coord.in_bounds() {
+                    return None;
+                }
+                planet.with_chunk(ChunkPos::of(coord), |chunk| chunk.swap(coord, cell_type));
+            }
+        }
+        planet.rebuild_spawn_index();
+
+        return Some(planet);
+    }
+
+    /// one pass over the `size` region chunk by chunk, filling in the free cells and how many cells each type has.
+    /// generating the terrain is the slow part, so bands of chunk rows are generated on all cores
+    fn rebuild_spawn_index(&mut self) {
+        let size = self.size as usize;
+        let side = self.size.div_ceil(CHUNK_SIZE as u32) as i32;
+        let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get()) as i32;
+        let band = (side / threads).max(1);
+
+        let planet: &Planet = self;
+        let chunks = planet.chunks.read().unwrap();
+        // every band fills the words of its own rows, a word shared with the next band is merged afterwards
+        let bands: Vec<(usize, Vec<u64>, HashMap<CellType, usize>)> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..side).step_by(band as usize).map(|first| {
+                let chunks = &chunks;
+                scope.spawn(move || {
+                    let rows = (first * CHUNK_SIZE) as usize..(((first + band) * CHUNK_SIZE) as usize).min(size);
+                    let offset = rows.start * size / 64;
+                    let mut free = vec![0u64; (rows.end * size).div_ceil(64) - offset];
+                    let mut counts = HashMap::new();
+
+                    for pos in (first..(first + band).min(side)).flat_map(|cy| (0..side).map(move |cx| ChunkPos { x: cx, y: cy })) {
+                        // generated chunks are only looked at, not kept
+                        let generated;
+                        let chunk = match chunks.get(&pos) {
+                            Some(chunk) => chunk,
+                            None => {
+                                generated = planet.generate_chunk(pos);
+                                &generated
+                            },
+                        };
+
+                        for y in pos.y * CHUNK_SIZE..((pos.y + 1) * CHUNK_SIZE).min(size as i32) {
+                            for x in pos.x * CHUNK_SIZE..((pos.x + 1) * CHUNK_SIZE).min(size as i32) {
+                                let cell_type = chunk.get(Coord::new(x, y));
+                                *counts.entry(cell_type).or_insert(0) += 1;
+                                if cell_type == CellType::Air {
+                                    let index = x as usize + y as usize * size;
+                                    free[index / 64 - offset] |= 1 << (index % 64);
+                                }
                             }
                         }
                     }
+                    return (offset, free, counts);
+                })
+            }).collect();
+
+            return workers.into_iter().map(|worker| worker.join().unwrap()).collect();
+        });
+        drop(chunks);
+
+        let mut free = vec![0u64; (size * size).div_ceil(64)];
+        let mut total = HashMap::new();
+        for (offset, words, counts) in bands {
+            for (index, word) in words.into_iter().enumerate() {
+                free[offset + index] |= word;
+            }
+            for (cell_type, count) in counts {
+                *total.entry(cell_type).or_insert(0) += count;
+            }
+        }
+        *self.spawn_index.get_mut().unwrap() = SpawnIndex::new(self.size, free, total);
+    }
+
+    pub fn seed(&self) -> u64 {
+        return self.seed;
+    }
+
+    /// the terrain a cell has before anyone touched it, only depends on the seed
+    fn generate_cell(&self, coord: Coord) -> CellType {
+        if !coord.in_bounds() {
+            return CellType::Bedrock;
+        }
+
+        let length = CELL_TYPES.len().pow(SCATTERNESS);
+        let index = length - (cell_random(self.seed, coord) % length as u64) as usize;
+
+        let noise_value = self.noise.get_noise(coord.x as f32 / 35.0, coord.y as f32 / 35.0);
+
+        if noise_value > 0.5 {
+            return CellType::Water;
+        }
+
+        if noise_value < 0.0 {
+            for (i, ct) in CELL_TYPES.iter().enumerate() {
+                let number = CELL_TYPES.len() - (i + 1);
+
+                if index > number.pow(SCATTERNESS) {
+                    return *ct;
                 }
+            }
+        }
+
+        return CellType::Air;
+    }
+
+    /// elevation of a cell between 0 and `MAX_HEIGHT`, digging does not change it
+    pub fn height(&self, coord: Coord) -> u8 {
+        if !coord.in_bounds() {
+            return MAX_HEIGHT;
+        }
+        if self.flat {
+            return 0;
+        }
 
-                cells.push(Cell::new(cell_type, x as i32, y as i32));
+        let noise_value = self.height_noise.get_noise(coord.x as f32 / 20.0, coord.y as f32 / 20.0);
+        let normalized = ((noise_value + 1.0) / 2.0).clamp(0.0, 1.0);
+
+        return (normalized * MAX_HEIGHT as f32).round() as u8;
+    }
+
+    fn generate_chunk(&self, pos: ChunkPos) -> Chunk {
+        let mut cells = Vec::with_capacity((CHUNK_SIZE * CHUNK_SIZE) as usize);
+
+        for y in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                cells.push(self.generate_cell(Coord::new(pos.x * CHUNK_SIZE + x, pos.y * CHUNK_SIZE + y)) as u8);
             }
         }
-        return Planet {
-            cells,
-            size
+
+        return Chunk::new(cells, false);
+    }
+
+    /// generates the chunks that are not loaded yet, outside of the write lock since that is the slow part
+    fn load_chunks(&self, positions: impl Iterator<Item = ChunkPos>) {
+        let chunks = self.chunks.read().unwrap();
+        let missing: Vec<ChunkPos> = positions.filter(|pos| !chunks.contains_key(pos)).collect();
+        drop(chunks);
+
+        if missing.is_empty() {
+            return;
+        }
+
+        let generated: Vec<(ChunkPos, Chunk)> = missing.into_iter().map(|pos| (pos, self.generate_chunk(pos))).collect();
+        let mut chunks = self.chunks.write().unwrap();
+        for (pos, chunk) in generated {
+            // someone else might have generated it in the meantime and already changed it
+            chunks.entry(pos).or_insert(chunk);
+        }
+    }
+
+    /// generates all chunks within `LOAD_RADIUS` of the given cell, nothing outside the world
+    pub fn load_around(&self, coord: Coord) {
+        if !coord.in_bounds() {
+            return;
         }
+        let center = ChunkPos::of(coord);
+
+        self.load_chunks((center.y - LOAD_RADIUS..=center.y + LOAD_RADIUS).flat_map(|cy| {
+            (center.x - LOAD_RADIUS..=center.x + LOAD_RADIUS).map(move |cx| ChunkPos { x: cx, y: cy })
+        }));
+    }
+
+    /// drops untouched chunks that are not close to any of the given positions, they can be generated again from the seed
+    pub fn unload_distant(&self, positions: &[Coord]) {
+        self.chunks.write().unwrap().retain(|pos, chunk| {
+            if chunk.modified.load(Ordering::Relaxed) {
+                return true;
+            }
+
+            return positions.iter().any(|coord| {
+                let center = ChunkPos::of(*coord);
+                (center.x - pos.x).abs() <= LOAD_RADIUS && (center.y - pos.y).abs() <= LOAD_RADIUS
+            });
+        });
+    }
+
+    /// iterates the cells of the `size` region row by row without copying the terrain
+    pub fn cells(&self) -> impl Iterator<Item = Cell> + '_ {
+        return self.cells_in(Coord::new(0, 0), self.size, self.size);
     }
 
-    pub fn cells(&self) -> Vec<Cell> {
-        return self.cells.clone();
+    /// iterates a rectangle of cells row by row, anything outside the loaded chunks is generated on the fly
+    pub fn cells_in(&self, origin: Coord, width: u32, height: u32) -> impl Iterator<Item = Cell> + '_ {
+        return (0..height as i32).flat_map(move |dy| {
+            (0..width as i32).map(move |dx| {
+                let coord = Coord::new(origin.x.saturating_add(dx), origin.y.saturating_add(dy));
+                self.get_cell(coord)
+            })
+        });
+    }
+
+    /// how many cells of each type are in the `size` region, kept up to date by every change
+    pub fn cell_counts(&self) -> HashMap<CellType, usize> {
+        return self.spawn_index.lock().unwrap().counts.clone();
+    }
+
+    /// a random air cell inside the `size` region, or None if the region is full
+    pub fn random_spawn(&self, rng: &mut impl Rng) -> Option<Coord> {
+        let spawn_index = self.spawn_index.lock().unwrap();
+        if spawn_index.free == 0 {
+            return None;
+        }
+
+        return spawn_index.nth(rng.gen_range(0..spawn_index.free));
     }
 
     //TODO: maybe delete? will i need this?
     #[allow(dead_code)]
     pub fn print_ascii(&self) -> Vec<u8> {
         let mut buffer: Vec<u8> = vec![];
-        for (index, cell) in self.cells.iter().enumerate() {
+        for (index, cell) in self.cells().enumerate() {
             if index != 0 && index % (self.size as usize) == 0 {
-                buffer.write(b"\n").unwrap();
+                buffer.write_all(b"\n").unwrap();
             }
             write!(&mut buffer, "{}", cell.cell_type).unwrap();
         }
@@ -83,10 +316,28 @@ impl Planet {
     }
 
     pub fn color_buffer(&self) -> Vec<u8> {
-        let mut buffer = vec![];
+        return self.fog_buffer(|_| true);
+    }
+
+    /// one `CellType` discriminant per cell of the `size` region, row by row
+    pub fn cell_type_buffer(&self) -> Vec<u8> {
+        return self.cells().map(|cell| cell.cell_type as u8).collect();
+    }
 
-        for cell in self.cells.iter() {
-            let cell_color = cell.cell_type.get_color();
+    /// changes whenever any cell changes, cheap enough to check on every request
+    pub fn version(&self) -> u64 {
+        return self.version.load(Ordering::Relaxed);
+    }
+
+    /// like `color_buffer` but cells for which `visible` returns false are drawn as fog
+    pub fn fog_buffer(&self, visible: impl Fn(&Coord) -> bool) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(self.size as usize * self.size as usize * 3);
+
+        for cell in self.cells() {
+            let cell_color = match visible(&cell.coord) {
+                true => self.cell_color(cell),
+                false => FOG_COLOR,
+            };
             buffer.push(cell_color.r);
             buffer.push(cell_color.g);
             buffer.push(cell_color.b);
@@ -95,72 +346,395 @@ impl Planet {
         return buffer;
     }
 
-    pub fn get_cell(&self, x: i32, y: i32) -> Option<&Cell>  {
-        if x < 0 || y < 0 {
-            return None;
-        }
-        
-        return self.cells.get(x as usize + y as usize * self.size as usize);
+    /// whether the cell is inside the `size` region shown on the web map
+    pub fn in_view(&self, coord: Coord) -> bool {
+        return coord.x >= 0 && coord.y >= 0 && coord.x < self.size as i32 && coord.y < self.size as i32;
     }
 
-    pub fn get_cell_type(&self, x: i32, y: i32) -> CellType  {
-        return match self.get_cell(x, y) {
-            Some(cell) => cell.cell_type,
-            None => CellType::Bedrock,
+    /// the color a cell is drawn with on the web map
+    pub fn cell_color(&self, cell: Cell) -> CellColor {
+        return match cell.cell_type {
+            CellType::Rover => cell.cell_type.get_color(),
+            cell_type => cell_type.get_color().shaded(self.height(cell.coord)),
         };
     }
 
-    pub fn set_celltype(&mut self, x: i32, y: i32, cell_type: CellType) {
-        self.cells[x as usize + y as usize * self.size as usize].cell_type = cell_type;
+    /// cells inside the `size` region that changed since the last call, with their current type
+    pub fn take_changes(&self) -> Vec<Cell> {
+        let changes: Vec<(Coord, CellType)> = self.changes.lock().unwrap().drain().collect();
+
+        return changes.into_iter()
+            .map(|(coord, original)| (self.get_cell(coord), original))
+            .filter(|(cell, original)| cell.cell_type != *original)
+            .map(|(cell, _)| cell)
+            .collect();
+    }
+
+    pub fn get_cell(&self, coord: Coord) -> Cell {
+        return Cell::new(self.get_cell_type(coord), coord);
+    }
+
+    pub fn get_cell_type(&self, coord: Coord) -> CellType {
+        if !coord.in_bounds() {
+            return CellType::Bedrock;
+        }
+
+        return match self.chunks.read().unwrap().get(&ChunkPos::of(coord)) {
+            Some(chunk) => chunk.get(coord),
+            None => self.generate_cell(coord),
+        };
+    }
+
+    pub fn set_celltype(&self, coord: Coord, cell_type: CellType) -> Result<(), PlanetError> {
+        if !coord.in_bounds() {
+            return Err(PlanetError::OutOfBounds(coord));
+        }
+
+        let original = self.with_chunk(ChunkPos::of(coord), |chunk| chunk.swap(coord, cell_type));
+        self.changed(coord, original, cell_type);
+
+        return Ok(());
+    }
+
+    /// changes the cell only if it still is `expected`, so two rovers can not both drive into or dig out the same cell.
+    /// `Ok(false)` means the cell was something else by now
+    pub fn replace(&self, coord: Coord, expected: CellType, cell_type: CellType) -> Result<bool, PlanetError> {
+        if !coord.in_bounds() {
+            return Err(PlanetError::OutOfBounds(coord));
+        }
+
+        if !self.with_chunk(ChunkPos::of(coord), |chunk| chunk.replace(coord, expected, cell_type)) {
+            return Ok(false);
+        }
+        self.changed(coord, expected, cell_type);
+
+        return Ok(true);
+    }
+
+    /// runs `f` on the chunk, generating it first if needed. the lock is held the whole time so `unload_distant` can not drop it in between
+    fn with_chunk<R>(&self, pos: ChunkPos, f: impl FnOnce(&Chunk) -> R) -> R {
+        if let Some(chunk) = self.chunks.read().unwrap().get(&pos) {
+            return f(chunk);
+        }
+
+        let generated = self.generate_chunk(pos);
+        let mut chunks = self.chunks.write().unwrap();
+        return f(chunks.entry(pos).or_insert(generated));
+    }
+
+    /// `original` and `cell_type` are exactly what one atomic write swapped, so the counts stay right whatever order changes arrive in
+    fn changed(&self, coord: Coord, original: CellType, cell_type: CellType) {
+        self.version.fetch_add(1, Ordering::Relaxed);
+        if !self.in_view(coord) {
+            return;
+        }
+        self.changes.lock().unwrap().entry(coord).or_insert(original);
+
+        // the current type and not the one just written, a concurrent change may have landed in between
+        let mut spawn_index = self.spawn_index.lock().unwrap();
+        spawn_index.set(coord, self.get_cell_type(coord) == CellType::Air);
+        spawn_index.count(original, cell_type);
+    }
+
+    /// writes the seed and every modified chunk to `dir`, rovers are stored as air and saved on their own by `GameServer::save`
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join("planet.txt"), format!("{} {}", self.seed, self.size))?;
+
+        // copied under the lock and written after it, so loading chunks never waits for the disk
+        let modified: HashMap<ChunkPos, Vec<u8>> = self.chunks.read().unwrap().iter()
+            .filter(|(_, chunk)| chunk.modified.load(Ordering::Relaxed))
+            .map(|(pos, chunk)| (*pos, chunk.cells.iter().map(|cell| match CellType::from_u8(cell.load(Ordering::Relaxed)) {
+                Some(CellType::Rover) => CellType::Air as u8,
+                _ => cell.load(Ordering::Relaxed),
+            }).collect()))
+            .collect();
+
+        for (pos, bytes) in modified.iter() {
+            fs::write(dir.join(format!("chunk_{}_{}.bin", pos.x, pos.y)), bytes)?;
+        }
+
+        // chunks of an earlier planet would otherwise come back on the next load
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let pos = match path.file_name().and_then(|name| name.to_str()).and_then(parse_chunk_name) {
+                Some(pos) => pos,
+                None => continue,
+            };
+
+            if !modified.contains_key(&pos) {
+                fs::remove_file(&path)?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    pub fn load(dir: &Path) -> io::Result<Planet> {
+        let header = fs::read_to_string(dir.join("planet.txt"))?;
+        let mut header = header.split_whitespace().map(|value| value.parse::<u64>());
+
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid planet.txt");
+        let seed = header.next().ok_or_else(invalid)?.map_err(|_| invalid())?;
+        let size = header.next().ok_or_else(invalid)?.map_err(|_| invalid())?;
+
+        let mut planet = Planet::unindexed(size as u32, seed);
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) => name,
+                None => continue,
+            };
+
+            let pos = match parse_chunk_name(name) {
+                Some(pos) => pos,
+                None => continue,
+            };
+
+            let bytes = fs::read(&path)?;
+            if bytes.len() != (CHUNK_SIZE * CHUNK_SIZE) as usize {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} has the wrong size", name)));
+            }
+
+            planet.chunks.get_mut().unwrap().insert(pos, Chunk::new(bytes, true));
+        }
+        planet.rebuild_spawn_index();
+
+        return Ok(planet);
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct Cell {
-    pub cell_type: CellType,
+impl Debug for Planet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Planet")
+            .field("seed", &self.seed)
+            .field("size", &self.size)
+            .field("loaded_chunks", &self.chunks.read().unwrap().len())
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Coord {
     pub x: i32,
     pub y: i32,
 }
 
-impl Cell {
-    pub fn new(cell_type: CellType, x: i32, y: i32) -> Cell {
-        return Cell { cell_type, x, y };
+impl Coord {
+    pub fn new(x: i32, y: i32) -> Coord {
+        return Coord { x, y };
+    }
+
+    /// the coordinate moved by dx, dy, or None if that would leave the world
+    pub fn offset(&self, dx: i32, dy: i32) -> Option<Coord> {
+        let coord = Coord::new(self.x.checked_add(dx)?, self.y.checked_add(dy)?);
+        if !coord.in_bounds() {
+            return None;
+        }
+        return Some(coord);
+    }
+
+    pub fn in_bounds(&self) -> bool {
+        // abs overflows for i32::MIN
+        return self.x.unsigned_abs() < WORLD_LIMIT as u32 && self.y.unsigned_abs() < WORLD_LIMIT as u32;
     }
 }
 
-#[derive(Debug, Clone, Copy, EnumIter, PartialEq, Eq)]
-pub enum CellType {
-    Air,
-    Rock,
-    Stone,
-    Bedrock,
-    Water,
-    Rover
+impl Display for Coord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "x:{} y:{}", self.x, self.y)
+    }
 }
 
-impl Display for CellType {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanetError {
+    OutOfBounds(Coord),
+}
+
+impl Display for PlanetError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            CellType::Air => f.write_char(' '),
-            CellType::Rock => f.write_char('.'),
-            CellType::Stone => f.write_char('o'),
-            CellType::Bedrock => f.write_char('X'),
-            CellType::Rover => f.write_char('R'),
-            CellType::Water => f.write_char('W'),
+            PlanetError::OutOfBounds(coord) => write!(f, "{} is outside the world", coord),
+        }
+    }
+}
+
+impl std::error::Error for PlanetError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl ChunkPos {
+    pub fn of(coord: Coord) -> ChunkPos {
+        return ChunkPos { x: coord.x.div_euclid(CHUNK_SIZE), y: coord.y.div_euclid(CHUNK_SIZE) };
+    }
+}
+
+/// one byte per cell holding the `CellType` discriminant
+#[derive(Debug)]
+struct Chunk {
+    cells: Box<[AtomicU8]>,
+    modified: AtomicBool,
+}
+
+impl Chunk {
+    fn new(cells: Vec<u8>, modified: bool) -> Chunk {
+        return Chunk { cells: cells.into_iter().map(AtomicU8::new).collect(), modified: AtomicBool::new(modified) };
+    }
+
+    fn get(&self, coord: Coord) -> CellType {
+        return CellType::from_u8(self.cells[chunk_index(coord)].load(Ordering::Relaxed)).unwrap_or(CellType::Air);
+    }
+
+    /// sets the cell and returns what it was before
+    fn swap(&self, coord: Coord, cell_type: CellType) -> CellType {
+        self.modified.store(true, Ordering::Relaxed);
+        let original = self.cells[chunk_index(coord)].swap(cell_type as u8, Ordering::Relaxed);
+        return CellType::from_u8(original).unwrap_or(CellType::Air);
+    }
+
+    fn replace(&self, coord: Coord, expected: CellType, cell_type: CellType) -> bool {
+        let replaced = self.cells[chunk_index(coord)].compare_exchange(expected as u8, cell_type as u8, Ordering::Relaxed, Ordering::Relaxed).is_ok();
+        if replaced {
+            self.modified.store(true, Ordering::Relaxed);
+        }
+        return replaced;
+    }
+}
+
+/// one bit per cell of the `size` region telling whether a rover can spawn there
+#[derive(Debug)]
+struct SpawnIndex {
+    bits: Vec<u64>,
+    /// a fenwick tree over the free cells of every word, so `nth` finds its word without walking all of them
+    tree: Vec<u32>,
+    size: u32,
+    free: usize,
+    /// how many cells of each type the `size` region has
+    counts: HashMap<CellType, usize>,
+}
+
+impl SpawnIndex {
+    fn new(size: u32, bits: Vec<u64>, counts: HashMap<CellType, usize>) -> SpawnIndex {
+        // tree[i] sums the words i - lowbit(i) + 1 ..= i, built bottom up in one pass
+        let mut tree = vec![0u32; bits.len() + 1];
+        for i in 1..tree.len() {
+            tree[i] += bits[i - 1].count_ones();
+            let parent = i + (i & i.wrapping_neg());
+            if parent < tree.len() {
+                tree[parent] += tree[i];
+            }
+        }
+
+        let free = bits.iter().map(|word| word.count_ones() as usize).sum();
+        return SpawnIndex { bits, tree, size, free, counts };
+    }
+
+    fn set(&mut self, coord: Coord, free: bool) {
+        if coord.x < 0 || coord.y < 0 || coord.x >= self.size as i32 || coord.y >= self.size as i32 {
+            return;
+        }
+
+        let index = coord.x as usize + coord.y as usize * self.size as usize;
+        let mask = 1u64 << (index % 64);
+        let word = &mut self.bits[index / 64];
+
+        if (*word & mask != 0) == free {
+            return;
+        }
+
+        if free {
+            *word |= mask;
+            self.free += 1;
+        } else {
+            *word &= !mask;
+            self.free -= 1;
+        }
+
+        let mut i = index / 64 + 1;
+        while i < self.tree.len() {
+            self.tree[i] = if free { self.tree[i] + 1 } else { self.tree[i] - 1 };
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    fn count(&mut self, original: CellType, cell_type: CellType) {
+        if let Some(count) = self.counts.get_mut(&original) {
+            *count = count.saturating_sub(1);
+        }
+        *self.counts.entry(cell_type).or_insert(0) += 1;
+    }
+
+    fn nth(&self, mut n: usize) -> Option<Coord> {
+        if n >= self.free {
+            return None;
+        }
+
+        // the last word whose prefix still holds at most n free cells, the one after it has the nth
+        let mut word_index = 0;
+        let mut step = (self.tree.len() - 1).checked_next_power_of_two().unwrap_or(0);
+        while step > 0 {
+            let next = word_index + step;
+            if next < self.tree.len() && (self.tree[next] as usize) <= n {
+                word_index = next;
+                n -= self.tree[next] as usize;
+            }
+            step /= 2;
+        }
+
+        let mut word = self.bits[word_index];
+        for _ in 0..n {
+            word &= word - 1;
         }
+
+        let index = word_index * 64 + word.trailing_zeros() as usize;
+        return Some(Coord::new((index % self.size as usize) as i32, (index / self.size as usize) as i32));
+    }
+}
+
+/// the chunk a `chunk_x_y.bin` file holds
+fn parse_chunk_name(name: &str) -> Option<ChunkPos> {
+    let coords: Vec<&str> = name.strip_prefix("chunk_")?.strip_suffix(".bin")?.split('_').collect();
+
+    return match coords.as_slice() {
+        [x, y] => Some(ChunkPos { x: x.parse().ok()?, y: y.parse().ok()? }),
+        _ => None,
+    };
+}
+
+fn chunk_index(coord: Coord) -> usize {
+    return (coord.x.rem_euclid(CHUNK_SIZE) + coord.y.rem_euclid(CHUNK_SIZE) * CHUNK_SIZE) as usize;
+}
+
+/// stateless random number for a cell so chunks can be generated in any order
+fn cell_random(seed: u64, coord: Coord) -> u64 {
+    let mut z = seed ^ ((coord.x as u32 as u64) << 32 | coord.y as u32 as u64);
+    z = z.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    return z ^ (z >> 31);
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Cell {
+    pub cell_type: CellType,
+    pub coord: Coord,
+}
+
+impl Cell {
+    pub fn new(cell_type: CellType, coord: Coord) -> Cell {
+        return Cell { cell_type, coord };
     }
 }
 
 impl CellTrait for CellType {
     fn get_color(&self) -> CellColor {
-        match self {
-            CellType::Air => CellColor { r: 250, g: 165, b: 0 },
-            CellType::Rock => CellColor { r: 128, g: 100, b: 64 },
-            CellType::Stone => CellColor { r: 64, g: 64, b: 64 },
-            CellType::Bedrock => CellColor { r: 0, g: 0, b: 0 },
-            CellType::Water => CellColor { r: 0, g: 0, b: 255 },
-            CellType::Rover => CellColor { r: 255, g: 0, b: 0 },
-        }
+        let [r, g, b] = self.rgb();
+        return CellColor { r, g, b };
     }
 
     fn mineable(&self) -> bool {
@@ -184,4 +758,20 @@ pub struct CellColor {
     r: u8,
     g: u8,
     b: u8,
-}
\ No newline at end of file
+}
+
+impl CellColor {
+    pub fn rgb(&self) -> [u8; 3] {
+        return [self.r, self.g, self.b];
+    }
+
+    /// darkens low ground and keeps high ground close to the original color
+    pub fn shaded(&self, height: u8) -> CellColor {
+        let factor = 0.5 + 0.5 * height as f32 / MAX_HEIGHT as f32;
+        return CellColor {
+            r: (self.r as f32 * factor) as u8,
+            g: (self.g as f32 * factor) as u8,
+            b: (self.b as f32 * factor) as u8,
+        };
+    }
+}
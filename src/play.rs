@@ -0,0 +1,92 @@
+use axum::extract::ws::{Message as WsMessage, WebSocket};
+use flume::Sender;
+use uuid::Uuid;
+use crate::limits::{IpSlot, Limiter, Verdict};
+use crate::{GameServer, Connection, Message};
+
+/// a browser rover client, every text frame is one protocol line and gets the same replies and limits a tcp client would.
+/// `slot` is given back when the socket closes
+pub async fn play(mut socket: WebSocket, sender: Sender<Message>, game: GameServer, slot: IpSlot) {
+    let _slot = slot;
+    let uuid = Uuid::new_v4();
+    let server_uuid = game.server_uuid;
+    let (client_send, client_recv) = flume::unbounded::<Message>();
+    let (reply_send, reply_recv) = flume::unbounded::<Message>();
+    let connection = Connection::new(client_send);
+    let mut limiter = Limiter::new(game.limits);
+    game.open_session(uuid, Some(connection.clone())).await;
+
+    loop {
+        tokio::select! {
+            _ = connection.kick.notified() => {
+                // let the client know why before hanging up
+                for reply in reply_recv.drain().chain(client_recv.drain()).filter(|reply| reply.target == uuid && !reply.data.is_empty()) {
+                    let _ = socket.send(frame(&reply.data)).await;
+                }
+                break;
+            }
+            message = socket.recv() => {
+                let line = match message {
+                    Some(Ok(WsMessage::Text(line))) => line,
+                    Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+
+                let data = line.trim().as_bytes().to_vec();
+                match limiter.check(&data) {
+                    Verdict::Accept => {},
+                    Verdict::Reject(reply) => {
+                        game.metrics.rejected();
+                        if socket.send(frame(&reply)).await.is_err() {
+                            break;
+                        }
+                        continue;
+                    },
+                    Verdict::Disconnect(reply) => {
+                        game.metrics.dropped();
+                        let _ = socket.send(frame(&reply)).await;
+                        break;
+                    },
+                }
+
+                if sender.send(Message { author: uuid, target: server_uuid, data, response: Some(reply_send.clone()) }).is_err() {
+                    break;
+                }
+            }
+            message = client_recv.recv_async() => {
+                let message = match message {
+                    Ok(message) => message,
+                    Err(_) => break,
+                };
+
+                if message.target != uuid || message.data.is_empty() {
+                    continue;
+                }
+
+                if socket.send(frame(&message.data)).await.is_err() {
+                    break;
+                }
+            }
+            reply = reply_recv.recv_async() => {
+                // this task holds a sender itself, so the channel never closes
+                let reply = reply.unwrap();
+                limiter.replied();
+
+                if reply.target != uuid || reply.data.is_empty() {
+                    continue;
+                }
+
+                if socket.send(frame(&reply.data)).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    let _ = sender.send(Message { author: uuid, target: server_uuid, data: "disconnect".as_bytes().to_vec(), response: None });
+}
+
+/// every websocket message is a line of its own, the line ending of server messages is left out
+fn frame(data: &[u8]) -> WsMessage {
+    return WsMessage::Text(String::from_utf8_lossy(data).trim_end_matches('\n').to_owned());
+}
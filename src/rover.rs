@@ -1,8 +1,26 @@
-use std::sync::Arc;
-use tokio::sync::Mutex;
-use crate::planet::{Planet, CellType, CellTrait};
+use std::collections::{HashSet, VecDeque};
+use serde_json::{json, Value};
+use crate::planet::{Planet, PlanetError, CellType, CellTrait, Coord, MAX_HEIGHT};
+use ma_rs_protocol::{Energy, Position, Scan};
 
-#[derive(Debug, Clone)]
+pub use ma_rs_protocol::Compass;
+
+pub const MAX_ENERGY: u32 = 100;
+/// energy regained for every command the rover receives
+pub const ENERGY_RECHARGE: u32 = 1;
+/// energy needed to move one cell on flat ground
+pub const MOVE_COST: u32 = 1;
+/// extra energy needed per unit of height climbed
+pub const CLIMB_COST: u32 = 2;
+/// how many of the latest positions a rover remembers for drawing its trail
+pub const TRAIL_LENGTH: usize = 32;
+/// the largest height difference a rover can drive up or down in one step
+pub const MAX_CLIMB: u8 = 3;
+/// `map` only shows explored cells this close to the rover, the reply is at most 129 by 129 cells
+pub const MAP_RADIUS: i32 = 64;
+
+/// plain data, every action gets the planet it happens on
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Rover {
     pub username: String,
     pub password: String,
@@ -10,114 +28,155 @@ pub struct Rover {
     pub y: i32,
     pub rotation: Compass,
     pub points: u32,
-    pub planet: Option<Arc<Mutex<Planet>>>,
+    pub energy: u32,
+    /// every cell this rover has scanned or driven over
+    pub explored: HashSet<Coord>,
+    /// the latest positions, oldest first
+    pub trail: VecDeque<Coord>,
+    /// driven by the server itself, see `bots`
+    pub bot: bool,
 }
 
 impl Rover {
-    pub async fn forward(&mut self) {
-        let mut motion = Vector2 { x: 0, y: 0};
-        match self.rotation {
-            Compass::North => motion.y -= 1,
-            Compass::East => motion.x += 1,
-            Compass::South => motion.y += 1,
-            Compass::West => motion.x -= 1,
+    pub fn coord(&self) -> Coord {
+        return Coord::new(self.x, self.y);
+    }
+    /// drives one cell ahead if it is free, flat enough and there is energy left, a blocked move is not an error
+    pub fn forward(&mut self, planet: &Planet) -> Result<(), PlanetError> {
+        let (dx, dy) = self.rotation.motion();
+        let position = self.coord();
+        let new_position = match position.offset(dx, dy) {
+            Some(new_position) => new_position,
+            None => return Ok(()),
+        };
+
+        let cell_type = planet.get_cell_type(new_position);
+
+        if cell_type != CellType::Air {
+            return Ok(());
         }
 
-        let planet = self.planet.as_mut().unwrap().lock().await;
+        let height = planet.height(position);
+        let new_height = planet.height(new_position);
 
-        let new_posotion = Vector2 {x: self.x + motion.x, y: self.y + motion.y};
+        if height.abs_diff(new_height) > MAX_CLIMB {
+            return Ok(());
+        }
 
-        let cell_type = planet.get_cell_type(new_posotion.x, new_posotion.y);
+        let cost = MOVE_COST + new_height.saturating_sub(height) as u32 * CLIMB_COST;
+        if self.energy < cost {
+            return Ok(());
+        }
 
-        if cell_type != CellType::Air {
-            return;
+        // another rover might be driving into the same cell right now
+        if !planet.replace(new_position, CellType::Air, CellType::Rover)? {
+            return Ok(());
         }
+        planet.set_celltype(position, CellType::Air)?;
 
-        self.x = new_posotion.x;
-        self.y = new_posotion.y;
-    }
-    pub async fn rotate(&mut self, clockwise: bool) {
-        if clockwise {
-            match self.rotation {
-                Compass::North => self.rotation = Compass::East,
-                Compass::East => self.rotation = Compass::South,
-                Compass::South => self.rotation = Compass::West,
-                Compass::West => self.rotation = Compass::North,
-            }
-        } else {
-            match self.rotation {
-                Compass::North => self.rotation = Compass::West,
-                Compass::East => self.rotation = Compass::North,
-                Compass::South => self.rotation = Compass::East,
-                Compass::West => self.rotation = Compass::South,
-            }
+        self.energy -= cost;
+        self.x = new_position.x;
+        self.y = new_position.y;
+        self.explored.insert(new_position);
+
+        self.trail.push_back(position);
+        if self.trail.len() > TRAIL_LENGTH {
+            self.trail.pop_front();
         }
+
+        return Ok(());
+    }
+    pub fn rotate(&mut self, clockwise: bool) {
+        self.rotation = self.rotation.rotated(clockwise);
     }
     pub fn position(&self) -> String {
-        return format!("Position x:{} y:{} Direction:{:?}", self.x, self.y, self.rotation);
+        return Position { x: self.x, y: self.y, direction: self.rotation }.to_string();
     }
-    pub async fn scan(&mut self) -> String {
-        let planet = self.planet.as_mut().unwrap().lock().await;
-
-        let mut scanline = String::new();
-        match self.rotation {
-            Compass::North => {
-                for index in -2..3 {
-                    scanline += &planet.get_cell_type(self.x + index, self.y - 2).to_string();
-                }
-                for index in -1..2 {
-                    scanline += &planet.get_cell_type(self.x + index, self.y - 1).to_string();
-                }
-            },
-            Compass::East => {
-                for index in -2..3 {
-                    scanline += &planet.get_cell_type(self.x + 2, self.y + index).to_string();
-                }
-                for index in -1..2 {
-                    scanline += &planet.get_cell_type(self.x + 1, self.y + index).to_string();
-                }
-            },
-            Compass::South => {
-                for index in -2..3 {
-                    scanline += &planet.get_cell_type(self.x + index, self.y + 2).to_string();
-                }
-                for index in -1..2 {
-                    scanline += &planet.get_cell_type(self.x + index, self.y + 1).to_string();
-                }
-            },
-            Compass::West => {
-                for index in -2..3 {
-                    scanline += &planet.get_cell_type(self.x - 2, self.y + index).to_string();
-                }
-                for index in -1..2 {
-                    scanline += &planet.get_cell_type(self.x - 1, self.y + index).to_string();
+    /// a css color that stays the same for a username
+    pub fn color(&self) -> String {
+        // fnv-1a, DefaultHasher is not guaranteed to be stable between builds
+        let mut hash: u32 = 0x811c9dc5;
+        for byte in self.username.bytes() {
+            hash = (hash ^ byte as u32).wrapping_mul(0x01000193);
+        }
+        return format!("hsl({}, 80%, 60%)", hash % 360);
+    }
+    pub fn energy(&self) -> String {
+        return Energy { energy: self.energy, max: MAX_ENERGY }.to_string();
+    }
+    pub fn recharge(&mut self) {
+        self.energy = (self.energy + ENERGY_RECHARGE).min(MAX_ENERGY);
+    }
+    pub fn scan(&mut self, planet: &Planet) -> String {
+        let coord = self.coord();
+        let offsets = Scan::offsets(self.rotation);
+
+        let mut cells = vec![];
+        for (dx, dy) in offsets {
+            let cell_type = match coord.offset(dx, dy) {
+                Some(coord) => {
+                    self.explored.insert(coord);
+                    planet.get_cell_type(coord)
+                },
+                None => CellType::Bedrock,
+            };
+            cells.push(cell_type);
+        }
+
+        let scan = Scan { far: cells[..5].try_into().unwrap(), near: cells[5..].try_into().unwrap() };
+        return scan.to_string();
+    }
+    /// heights of the same cells as `scan`, separated by spaces
+    pub fn scan_height(&self, planet: &Planet) -> String {
+        let coord = self.coord();
+        let offsets = Scan::offsets(self.rotation);
+
+        let heights: Vec<String> = offsets.iter().map(|(dx, dy)| {
+            let height = match coord.offset(*dx, *dy) {
+                Some(coord) => planet.height(coord),
+                None => MAX_HEIGHT,
+            };
+            height.to_string()
+        }).collect();
+
+        return heights.join(" ");
+    }
+    /// the explored area within `MAP_RADIUS` as a header line followed by one line per row, unexplored cells are `?`
+    pub fn map(&self, planet: &Planet) -> String {
+        let near: Vec<&Coord> = self.explored.iter()
+            .filter(|coord| coord.x.abs_diff(self.x) <= MAP_RADIUS as u32 && coord.y.abs_diff(self.y) <= MAP_RADIUS as u32)
+            .collect();
+        let min_x = near.iter().map(|coord| coord.x).min().unwrap_or(self.x);
+        let max_x = near.iter().map(|coord| coord.x).max().unwrap_or(self.x);
+        let min_y = near.iter().map(|coord| coord.y).min().unwrap_or(self.y);
+        let max_y = near.iter().map(|coord| coord.y).max().unwrap_or(self.y);
+
+        let mut map = format!("Map x:{} y:{} width:{} height:{}", min_x, min_y, max_x - min_x + 1, max_y - min_y + 1);
+        for y in min_y..=max_y {
+            map.push('\n');
+            for x in min_x..=max_x {
+                let coord = Coord::new(x, y);
+                if self.explored.contains(&coord) {
+                    map += &planet.get_cell_type(coord).to_string();
+                } else {
+                    map.push('?');
                 }
-            },
+            }
         }
-        
-        return scanline;
+
+        return map;
     }
-    pub async fn dig(&mut self) {
-        let mut planet = self.planet.as_mut().unwrap().lock().await;
-        
-        let motion = match self.rotation {
-            Compass::North => Vector2 {x: 0, y: -1},
-            Compass::East => Vector2 {x: 1, y: 0},
-            Compass::South => Vector2 {x: 0, y: 1},
-            Compass::West => Vector2 {x: -1, y: 0},
-        };
+    /// what was dug out in front of the rover and the points it was worth
+    pub fn dig(&mut self, planet: &Planet) -> Option<(CellType, u32)> {
+        let (dx, dy) = self.rotation.motion();
+        let front = self.coord().offset(dx, dy)?;
 
-        let cell_front = planet.get_cell(self.x + motion.x, self.y + motion.y);
+        let cell_front = planet.get_cell(front);
         
         //println!("front: {:#?}", cell_front);
 
-        if cell_front.is_none() {
-            return;
-        }
-        let cell_front = cell_front.unwrap();
-
         if !cell_front.cell_type.mineable() {
-            return;
+            return None;
         }
         
         let price = match cell_front.cell_type {
@@ -129,38 +188,69 @@ impl Rover {
             CellType::Water => 0,
         };
 
-        let cell_x = cell_front.x;
-        let cell_y = cell_front.y;
-
         //println!("updated");
-        planet.set_celltype(cell_x, cell_y, CellType::Air);
+        // only one of two rovers digging the same cell gets the points
+        if planet.replace(cell_front.coord, cell_front.cell_type, CellType::Air) != Ok(true) {
+            return None;
+        }
         self.points += price;
+
+        return Some((cell_front.cell_type, price));
     }
 }
 
 
-#[derive(Debug, Clone)]
-pub enum Compass {
-    North,
-    East,
-    South,
-    West,
-}
+impl Rover {
+    pub fn new(username: String, password: String, x: i32, y: i32) -> Self {
+        let explored = HashSet::from([Coord::new(x, y)]);
+        Self { username, password, x, y, explored, ..Default::default() }
+    }
 
-#[derive(Debug, Clone)]
-struct Vector2 {
-    pub x: i32,
-    pub y: i32,
-}
+    /// what is saved of a rover between restarts, the trail is only drawn while it drives
+    pub fn to_json(&self) -> Value {
+        let explored: Vec<[i32; 2]> = self.explored.iter().map(|coord| [coord.x, coord.y]).collect();
+        return json!({
+            "username": self.username,
+            "password": self.password,
+            "x": self.x,
+            "y": self.y,
+            "rotation": format!("{:?}", self.rotation),
+            "points": self.points,
+            "energy": self.energy,
+            "explored": explored,
+            "bot": self.bot,
+        });
+    }
 
-impl Rover {
-    pub fn new(username: String, password: String, x: i32, y: i32, planet: Arc<Mutex<Planet>>) -> Self {
-        Self { username, password, x, y, planet: Some(planet), ..Default::default() }
+    /// a rover written by `to_json`, None if anything is missing or has the wrong type
+    pub fn from_json(value: &Value) -> Option<Rover> {
+        let int = |key: &str| value[key].as_i64().and_then(|number| i32::try_from(number).ok());
+        let uint = |key: &str| value[key].as_u64().and_then(|number| u32::try_from(number).ok());
+
+        let mut explored = HashSet::new();
+        for coord in value["explored"].as_array()? {
+            let x = i32::try_from(coord[0].as_i64()?).ok()?;
+            let y = i32::try_from(coord[1].as_i64()?).ok()?;
+            explored.insert(Coord::new(x, y));
+        }
+
+        return Some(Rover {
+            username: value["username"].as_str()?.to_owned(),
+            password: value["password"].as_str()?.to_owned(),
+            x: int("x")?,
+            y: int("y")?,
+            rotation: value["rotation"].as_str()?.parse().ok()?,
+            points: uint("points")?,
+            energy: uint("energy")?,
+            explored,
+            trail: VecDeque::new(),
+            bot: value["bot"].as_bool()?,
+        });
     }
 }
 
 impl Default for Rover {
     fn default() -> Self {
-        Self { x: Default::default(), y: Default::default(), points: Default::default(), username: "".into(), password: "".into(), rotation: Compass::North, planet: None }
+        Self { x: Default::default(), y: Default::default(), points: Default::default(), username: "".into(), password: "".into(), rotation: Compass::North, energy: MAX_ENERGY, explored: HashSet::new(), trail: VecDeque::new(), bot: false }
     }
 }
\ No newline at end of file
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Instant;
+use flume::{Receiver, Sender};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use uuid::Uuid;
+use crate::client::handle_client;
+use crate::limits::Limiter;
+use crate::{Connection, GameServer, Message};
+use ma_rs_protocol::TOO_MANY_CONNECTIONS;
+
+/// accepts rover clients on the 6969 protocol until the task is aborted
+pub async fn accept(game: GameServer, listener: TcpListener, sender: Sender<Message>) {
+    loop {
+        let (mut stream, sock_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(error) => {
+                println!("failed to accept a connection: {}", error);
+                continue;
+            },
+        };
+
+        let slot = match game.ip_slots.take(sock_addr.ip(), game.limits.connections_per_ip) {
+            Some(slot) => slot,
+            None => {
+                println!("refused a connection from {}, it has too many open", sock_addr.ip());
+                game.metrics.dropped();
+                tokio::spawn(async move {
+                    let _ = stream.write_all(TOO_MANY_CONNECTIONS.as_bytes()).await;
+                });
+                continue;
+            },
+        };
+
+        let client_uuid = Uuid::new_v4();
+        let (client_send, client_recv) = flume::unbounded::<Message>();
+        let connection = Connection::new(client_send);
+        game.open_session(client_uuid, Some(connection.clone())).await;
+        game.metrics.connection();
+        handle_client(stream, client_uuid, game.server_uuid, sender.clone(), connection, client_recv, game.metrics.clone(), Limiter::new(game.limits), slot);
+    }
+}
+
+/// hands every message from the connections to the worker of its session until `shutdown` resolves,
+/// sessions run at the same time while the commands of one session stay in order
+pub async fn dispatch(game: &GameServer, receiver: Receiver<Message>, shutdown: impl Future<Output = ()>) {
+    tokio::pin!(shutdown);
+    let mut workers: HashMap<Uuid, Sender<Message>> = HashMap::new();
+
+    loop {
+        let message = tokio::select! {
+            message = receiver.recv_async() => match message {
+                Ok(message) => message,
+                // every sender is gone, nobody can talk to the game anymore
+                Err(_) => return,
+            },
+            _ = &mut shutdown => return,
+        };
+
+        if message.target != game.server_uuid {
+            continue;
+        }
+
+        // the last message the worker of this session will get
+        let closing = message.response.is_none() && message.data == b"disconnect";
+        let author = message.author;
+
+        // anyone can make up an author over http, only sessions the registry knows get a worker
+        if !workers.contains_key(&author) && !game.sessions.with(move |sessions| sessions.get(author).is_some()).await {
+            handle(game, message).await;
+            continue;
+        }
+
+        let worker = workers.entry(author).or_insert_with(|| {
+            let (sender, receiver) = flume::unbounded();
+            tokio::spawn(work(game.clone(), receiver));
+            sender
+        });
+        // counted before sending, the worker might take it up right away
+        game.metrics.queued(true);
+        if worker.send(message).is_err() {
+            game.metrics.queued(false);
+        }
+
+        if closing {
+            workers.remove(&author);
+        }
+    }
+}
+
+/// runs the messages of one session through the game, one at a time, until the session is closed
+async fn work(game: GameServer, receiver: Receiver<Message>) {
+    game.metrics.worker(true);
+    while let Ok(message) = receiver.recv_async().await {
+        game.metrics.queued(false);
+        handle(&game, message).await;
+    }
+    game.metrics.worker(false);
+}
+
+/// one message through the game, commands that expect a reply always get one
+async fn handle(game: &GameServer, message: Message) {
+    let message_string = match String::from_utf8(message.data) {
+        Ok(message) => message,
+        Err(_) => {
+            println!("this is not utf8");
+            // still a command as far as the connection is concerned, it waits for a reply
+            if let Some(response) = message.response {
+                let _ = response.send(Message { author: game.server_uuid, target: message.author, data: vec![], response: None });
+            }
+            return;
+        },
+    };
+
+    let started = Instant::now();
+    match message.response {
+        // every command gets exactly one reply, an empty one if there is nothing to say
+        Some(response) => {
+            let reply = game.handle_command(message.author, &message_string).await;
+            let _ = response.send(Message { author: game.server_uuid, target: message.author, data: reply.into_bytes(), response: None });
+        },
+        // a disconnect nobody waits for means the connection itself is gone
+        None if message_string == "disconnect" => game.close_session(message.author).await,
+        None => {
+            game.handle_command(message.author, &message_string).await;
+        },
+    }
+    game.metrics.command(&message_string, started.elapsed());
+}
@@ -0,0 +1,174 @@
+//! who is connected and every rover the server knows, owned by a single task so lookups never lock
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use flume::Sender;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+use crate::game::Connection;
+use crate::rover::Rover;
+
+/// a rover is locked on its own while a command runs, so the registry never waits for the planet
+pub type RoverHandle = Arc<Mutex<Rover>>;
+
+type Job = Box<dyn FnOnce(&mut Sessions) + Send>;
+
+/// one tcp connection, http login, websocket or bot
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub uuid: Uuid,
+    /// None for http sessions, which only ever get replies
+    pub connection: Option<Connection>,
+    /// driven by the server itself, see `bots`
+    pub bot: bool,
+    /// the rover this session is logged in as
+    pub username: Option<String>,
+}
+
+/// what a session can do next
+#[derive(Debug)]
+pub enum Driver {
+    Unknown,
+    LoggedOut { bot: bool },
+    Driving(RoverHandle),
+}
+
+/// the session state itself, only ever touched by the owner task through `SessionRegistry::with`
+#[derive(Debug, Default)]
+pub struct Sessions {
+    sessions: HashMap<Uuid, Session>,
+    /// every rover by username, online or not
+    rovers: HashMap<String, RoverHandle>,
+    /// username to the session that is driving the rover
+    drivers: HashMap<String, Uuid>,
+}
+
+impl Sessions {
+    pub fn open(&mut self, session: Session) {
+        self.sessions.insert(session.uuid, session);
+    }
+
+    /// forgets the session, returns the username of the rover that went offline with it
+    pub fn close(&mut self, session: Uuid) -> Option<String> {
+        let username = self.sessions.remove(&session)?.username?;
+        self.drivers.remove(&username);
+        return Some(username);
+    }
+
+    pub fn get(&self, session: Uuid) -> Option<&Session> {
+        return self.sessions.get(&session);
+    }
+
+    pub fn sessions(&self) -> impl Iterator<Item = &Session> {
+        return self.sessions.values();
+    }
+
+    pub fn driver(&self, session: Uuid) -> Driver {
+        let session = match self.sessions.get(&session) {
+            Some(session) => session,
+            None => return Driver::Unknown,
+        };
+
+        return match session.username.as_ref().and_then(|username| self.rovers.get(username)) {
+            Some(rover) => Driver::Driving(rover.clone()),
+            None => Driver::LoggedOut { bot: session.bot },
+        };
+    }
+
+    /// the rover and whether someone is driving it
+    pub fn rover(&self, username: &str) -> Option<(RoverHandle, bool)> {
+        let rover = self.rovers.get(username)?;
+        return Some((rover.clone(), self.drivers.contains_key(username)));
+    }
+
+    /// every rover and whether it is online
+    pub fn rovers(&self) -> Vec<(RoverHandle, bool)> {
+        return self.rovers.iter().map(|(username, rover)| (rover.clone(), self.drivers.contains_key(username))).collect();
+    }
+
+    pub fn online(&self) -> usize {
+        return self.drivers.len();
+    }
+
+    pub fn offline(&self) -> usize {
+        return self.rovers.len() - self.drivers.len();
+    }
+
+    /// false if there already is a rover with that name
+    pub fn add_rover(&mut self, username: String, rover: RoverHandle) -> bool {
+        if self.rovers.contains_key(&username) {
+            return false;
+        }
+        self.rovers.insert(username, rover);
+        return true;
+    }
+
+    /// lets the session drive the rover, false if the session is gone, already driving or the rover is taken
+    pub fn attach(&mut self, session: Uuid, username: &str) -> bool {
+        let client = match self.sessions.get_mut(&session) {
+            Some(client) if client.username.is_none() => client,
+            _ => return false,
+        };
+        if !self.rovers.contains_key(username) || self.drivers.contains_key(username) {
+            return false;
+        }
+
+        client.username = Some(username.to_owned());
+        self.drivers.insert(username.to_owned(), session);
+        return true;
+    }
+
+    /// the session keeps its connection but no longer drives a rover, returns the username of that rover
+    pub fn logout(&mut self, session: Uuid) -> Option<String> {
+        let username = self.sessions.get_mut(&session)?.username.take()?;
+        self.drivers.remove(&username);
+        return Some(username);
+    }
+
+    /// logs out whoever drives the rover and returns their session
+    pub fn logout_rover(&mut self, username: &str) -> Option<Session> {
+        let session = self.drivers.remove(username)?;
+        let session = self.sessions.get_mut(&session)?;
+        session.username = None;
+        return Some(session.clone());
+    }
+
+    /// forgets every session, all rovers go offline
+    pub fn drain(&mut self) -> Vec<Session> {
+        self.drivers.clear();
+        return self.sessions.drain().map(|(_, session)| session).collect();
+    }
+}
+
+/// the handle every part of the server uses to reach the sessions
+#[derive(Debug, Clone)]
+pub struct SessionRegistry {
+    jobs: Sender<Job>,
+}
+
+impl SessionRegistry {
+    /// starts the owner task, it stops once every handle is dropped
+    pub fn spawn() -> SessionRegistry {
+        let (jobs, receiver) = flume::unbounded::<Job>();
+
+        tokio::spawn(async move {
+            let mut sessions = Sessions::default();
+            while let Ok(job) = receiver.recv_async().await {
+                job(&mut sessions);
+            }
+        });
+
+        return SessionRegistry { jobs };
+    }
+
+    /// runs `f` on the owner task, `f` must not block since every other session waits for it
+    pub async fn with<R: Send + 'static>(&self, f: impl FnOnce(&mut Sessions) -> R + Send + 'static) -> R {
+        let (sender, receiver) = flume::bounded(1);
+        let job: Job = Box::new(move |sessions| {
+            let _ = sender.send(f(sessions));
+        });
+
+        self.jobs.send(job).expect("the session registry stopped");
+        return receiver.recv_async().await.expect("the session registry stopped");
+    }
+}
@@ -0,0 +1,141 @@
+//! everything on the web port besides the http api and the admin api, the map, the browser client and the probes
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use axum::{extract::{self, ConnectInfo, Query, WebSocketUpgrade}, http::{header, HeaderMap, HeaderValue, StatusCode}, response::{IntoResponse, Response}, routing::get, Json, Router};
+use flume::Sender;
+use serde_json::{json, Value};
+use tower_http::services::ServeDir;
+use crate::{live, maps, metrics, play, GameServer, Message};
+use ma_rs_protocol::TOO_MANY_CONNECTIONS;
+
+/// the static files of the web client
+pub const WEB_DIR: &str = "web";
+
+/// GET /planet, the colors of every cell. ?rover=name only shows what that rover has explored
+async fn planet(game: GameServer, params: HashMap<String, String>, headers: HeaderMap) -> Response {
+    let explored = match params.get("rover") {
+        Some(username) => match game.find_rover(username).await {
+            Some(rover) => Some(rover.explored),
+            None => return (StatusCode::NOT_FOUND, "no such rover").into_response(),
+        },
+        None => None,
+    };
+
+    let planet = game.planet.read().await;
+    let (board, etag) = match explored {
+        Some(explored) => (planet.fog_buffer(|coord| explored.contains(coord)), None),
+        None => {
+            let etag = maps::etag(&planet, "json");
+            if maps::not_modified(&headers, &etag) {
+                return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+            }
+            (planet.color_buffer(), Some(etag))
+        },
+    };
+    let response = json!({
+        "board": board,
+        "planet_size": planet.size,
+    });
+
+    let mut response = serde_json::to_string(&response).unwrap().into_response();
+    if let Some(etag) = etag {
+        response.headers_mut().insert(header::ETAG, HeaderValue::from_str(&etag).unwrap());
+    }
+    return response;
+}
+
+/// GET /rovers, every rover with what the map needs to draw it
+async fn rovers(game: GameServer) -> Json<Vec<Value>> {
+    let rovers: Vec<Value> = game.all_rovers().await.iter().map(|(rover, online)| json!({
+        "username": rover.username,
+        "x": rover.x,
+        "y": rover.y,
+        "rotation": format!("{:?}", rover.rotation),
+        "points": rover.points,
+        "energy": rover.energy,
+        "color": rover.color(),
+        "trail": rover.trail.iter().map(|coord| [coord.x, coord.y]).collect::<Vec<[i32; 2]>>(),
+        "online": online,
+        "bot": rover.bot,
+    })).collect();
+
+    return Json(rovers);
+}
+
+/// GET /leaderboard, most points first
+async fn leaderboard(game: GameServer) -> Json<Vec<Value>> {
+    let mut rovers = game.all_rovers().await;
+    rovers.sort_by(|(a, _), (b, _)| b.points.cmp(&a.points).then_with(|| a.username.cmp(&b.username)));
+
+    let leaderboard: Vec<Value> = rovers.iter().enumerate().map(|(index, (rover, online))| json!({
+        "rank": index + 1,
+        "username": rover.username,
+        "points": rover.points,
+        "online": online,
+        "bot": rover.bot,
+    })).collect();
+
+    return Json(leaderboard);
+}
+
+/// the web client, the map and the probes. `sender` is the message channel the browser client plays through,
+/// `ready` is what /readyz answers
+pub fn router(game: GameServer, sender: Sender<Message>, ready: Arc<AtomicBool>) -> Router {
+    let live = game.clone();
+    let planet_state = game.clone();
+    let play_state = game.clone();
+    let rovers_state = game.clone();
+    let leaderboard_state = game.clone();
+    let png = game.planet.clone();
+    let bin = game.planet.clone();
+    let tiles = game.planet.clone();
+    let cell = game.planet.clone();
+    let metrics_state = game;
+    let metrics_sender = sender.clone();
+
+    return Router::new()
+        .nest_service("/", ServeDir::new(WEB_DIR))
+        .route("/live", get(move |ws: WebSocketUpgrade| async move {
+            ws.on_upgrade(move |socket| live::stream(socket, live.planet.clone(), live.live_sender.subscribe()))
+        }))
+        .route("/planet", get(move |Query(params): Query<HashMap<String, String>>, headers: HeaderMap| {
+            planet(planet_state.clone(), params, headers)
+        }))
+        .route("/play", get(move |ws: WebSocketUpgrade, ConnectInfo(addr): ConnectInfo<SocketAddr>| async move {
+            // taken before the upgrade so a refused browser gets a plain http error
+            match play_state.ip_slots.take(addr.ip(), play_state.limits.connections_per_ip) {
+                Some(slot) => ws.on_upgrade(move |socket| play::play(socket, sender, play_state, slot)),
+                None => {
+                    play_state.metrics.dropped();
+                    (StatusCode::TOO_MANY_REQUESTS, TOO_MANY_CONNECTIONS).into_response()
+                },
+            }
+        }))
+        .route("/rovers", get(move || rovers(rovers_state.clone())))
+        .route("/leaderboard", get(move || leaderboard(leaderboard_state.clone())))
+        .route("/planet.png", get(move |Query(params): Query<HashMap<String, String>>, headers: HeaderMap| {
+            maps::planet_png(png.clone(), params, headers)
+        }))
+        .route("/planet.bin", get(move |headers: HeaderMap| {
+            maps::planet_bin(bin.clone(), headers)
+        }))
+        .route("/tiles/:z/:x/:y", get(move |extract::Path(tile): extract::Path<(u32, i32, String)>, headers: HeaderMap| {
+            maps::tile(tiles.clone(), tile, headers)
+        }))
+        .route("/cell", get(move |Query(params): Query<HashMap<String, String>>| {
+            maps::cell(cell.clone(), params)
+        }))
+        .route("/healthz", get(|| async { "ok" }))
+        .route("/readyz", get(move || async move {
+            match ready.load(Ordering::Relaxed) {
+                true => (StatusCode::OK, "ready"),
+                false => (StatusCode::SERVICE_UNAVAILABLE, "not ready"),
+            }
+        }))
+        .route("/metrics", get(move || async move {
+            ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], metrics::render(&metrics_state, metrics_sender.len()).await)
+        }));
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{http, TestServer, ARENA};
+use ma_rs::admin::{self, AdminCommand};
+use ma_rs::planet::{CellType, Coord, Planet};
+use ma_rs::rover::Compass;
+use ma_rs::GameServer;
+use serde_json::json;
+use uuid::Uuid;
+
+const TOKEN: &str = "admin-token";
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn regenerating_onto_a_full_planet_leaves_every_rover_where_it_was() {
+    // a planet of nothing but air, every cell gets a rover and any generated planet has less room
+    let map: Vec<String> = (0..10).map(|_| " ".repeat(10)).collect();
+    let game = GameServer::new(Planet::from_ascii(1, &map.join("\n")).unwrap());
+    for index in 0..100 {
+        let session = Uuid::new_v4();
+        game.open_session(session, None).await;
+        assert_eq!(game.handle_command(session, &format!("login rover{} password", index)).await.0, "login successful");
+    }
+    let before = game.all_rovers().await;
+    let seed = game.planet.read().await.seed();
+
+    assert!(admin::run(&game, AdminCommand::Regenerate(Some(2))).await.is_err());
+
+    assert_eq!(game.planet.read().await.seed(), seed);
+    for (rover, _) in before {
+        let after = game.find_rover(&rover.username).await.unwrap();
+        assert_eq!(after.coord(), rover.coord());
+        assert_eq!(game.planet.read().await.get_cell_type(rover.coord()), CellType::Rover);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn teleporting_past_the_end_of_i32_is_rejected() {
+    let server = TestServer::start(&ARENA).await;
+    let _bob = server.rover_at("bob", Coord::new(3, 3), Compass::North).await;
+    let router = admin::router(server.game.clone(), TOKEN.to_owned());
+
+    // 2^32 + 4 would wrap around to 4, a free cell of the arena
+    let body = json!({ "username": "bob", "x": 4294967300i64, "y": 3 });
+    let (status, reply) = http(&router, Method::POST, "/admin/teleport", Some(TOKEN), Some(body)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(reply["error"], "4294967300 3 is not a coordinate");
+    assert_eq!(server.rover("bob").await.coord(), Coord::new(3, 3));
+
+    let body = json!({ "username": "bob", "x": 4, "y": 3 });
+    let (status, _) = http(&router, Method::POST, "/admin/teleport", Some(TOKEN), Some(body)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(server.rover("bob").await.coord(), Coord::new(4, 3));
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use std::time::Duration;
+use axum::http::{Method, StatusCode};
+use common::{http, TestServer, ARENA, TIMEOUT};
+use ma_rs::api::{self, IDLE_TIMEOUT};
+use ma_rs::limits::Limits;
+use ma_rs::planet::Coord;
+use ma_rs::rover::Compass;
+use serde_json::json;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn commands_that_change_the_world_need_a_post() {
+    let server = TestServer::start(&ARENA).await;
+    let router = api::router(server.sender.clone(), server.game.clone());
+
+    let (status, reply) = http(&router, Method::POST, "/api/login", None, Some(json!({ "username": "bob", "password": "password" }))).await;
+    assert_eq!(status, StatusCode::OK);
+    let token = reply["token"].as_str().unwrap().to_owned();
+    let before = server.rover("bob").await;
+
+    for command in ["forward", "turnleft", "turnright", "dig"] {
+        let (status, _) = http(&router, Method::GET, &format!("/api/{}", command), Some(&token), None).await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED, "GET /api/{}", command);
+    }
+    assert_eq!(server.rover("bob").await, before);
+
+    let (status, reply) = http(&router, Method::GET, "/api/position", Some(&token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(reply["reply"], before.position());
+
+    let (status, _) = http(&router, Method::POST, "/api/turnright", Some(&token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(server.rover("bob").await.rotation, before.rotation.rotated(true));
+}
+
+#[tokio::test(start_paused = true)]
+async fn idle_tokens_are_logged_out() {
+    let server = TestServer::start(&ARENA).await;
+    let router = api::router(server.sender.clone(), server.game.clone());
+
+    let mut tokens = vec![];
+    for username in ["idle", "busy"] {
+        let (status, reply) = http(&router, Method::POST, "/api/login", None, Some(json!({ "username": username, "password": "password" }))).await;
+        assert_eq!(status, StatusCode::OK);
+        tokens.push(reply["token"].as_str().unwrap().to_owned());
+    }
+
+    // only the busy token is used while the time passes
+    let step = Duration::from_secs(60);
+    for _ in 0..IDLE_TIMEOUT.as_secs() / step.as_secs() + 1 {
+        tokio::time::sleep(step).await;
+        let (status, _) = http(&router, Method::GET, "/api/energy", Some(&tokens[1]), None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    server.wait_offline("idle").await;
+    let (status, _) = http(&router, Method::GET, "/api/energy", Some(&tokens[0]), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (_, online) = server.game.sessions.with(|sessions| sessions.rover("busy")).await.unwrap();
+    assert!(online);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn logins_share_the_address_limit_with_connections() {
+    let server = TestServer::start_with_limits(&ARENA, Limits { connections_per_ip: 1, ..Limits::default() }).await;
+    let router = api::router(server.sender.clone(), server.game.clone());
+    let login = json!({ "username": "bob", "password": "password" });
+
+    let mut client = server.connect().await;
+    assert_eq!(client.ask("position").await, "not signed in");
+    let (status, reply) = http(&router, Method::POST, "/api/login", None, Some(login.clone())).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(reply["error"], "too many connections");
+
+    // the slot comes back once the server notices the connection is gone
+    drop(client);
+    let token = tokio::time::timeout(TIMEOUT, async {
+        loop {
+            let (status, reply) = http(&router, Method::POST, "/api/login", None, Some(login.clone())).await;
+            if status == StatusCode::OK {
+                return reply["token"].as_str().unwrap().to_owned();
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }).await.expect("the connection never gave its slot back");
+
+    let (status, _) = http(&router, Method::POST, "/api/login", None, Some(json!({ "username": "alice", "password": "password" }))).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    let (status, _) = http(&router, Method::POST, "/api/logout", Some(&token), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = http(&router, Method::POST, "/api/login", None, Some(json!({ "username": "alice", "password": "password" }))).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn logout_leaves_other_sessions_alone() {
+    let server = TestServer::start(&ARENA).await;
+    let router = api::router(server.sender.clone(), server.game.clone());
+    let mut client = server.rover_at("bob", Coord::new(3, 3), Compass::North).await;
+
+    // the uuid of the tcp session, as an admin would see it
+    let uuid = server.game.sessions.with(|sessions| sessions.sessions().next().unwrap().uuid).await;
+    let (status, _) = http(&router, Method::POST, "/api/logout", Some(&uuid.to_string()), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    assert_eq!(client.ask("position").await, "Position x:3 y:3 Direction:North");
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use std::time::Duration;
+use common::{TestServer, ARENA, TIMEOUT};
+use ma_rs::bots::{self, Strategy};
+
+/// bots in tests do not wait between moves
+const TICK: Duration = Duration::from_millis(1);
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn stone_bot_digs_the_stone() {
+    let server = TestServer::start(&ARENA).await;
+    tokio::spawn(bots::run(server.game.clone(), server.sender.clone(), "bot-stone-1".to_owned(), Strategy::Stone, TICK));
+
+    tokio::time::timeout(TIMEOUT, async {
+        while server.game.find_rover("bot-stone-1").await.map_or(0, |rover| rover.points) < 100 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }).await.expect("the bot never dug the stone");
+
+    assert!(server.rover("bot-stone-1").await.bot);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn every_strategy_keeps_moving() {
+    let server = TestServer::start(&ARENA).await;
+    for strategy in [Strategy::RandomWalk, Strategy::Spiral] {
+        let username = format!("bot-{}-1", strategy.name());
+        tokio::spawn(bots::run(server.game.clone(), server.sender.clone(), username, strategy, TICK));
+    }
+
+    // three commands a move
+    tokio::time::timeout(TIMEOUT, async {
+        while server.game.metrics.processed() < 300 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }).await.expect("the bots stopped playing");
+
+    for (rover, online) in server.game.all_rovers().await {
+        assert!(rover.bot && online, "{} is not an online bot", rover.username);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn players_can_not_pose_as_bots() {
+    let server = TestServer::start(&ARENA).await;
+    let mut client = server.connect().await;
+
+    assert_eq!(client.ask("login bot-stone-1 password").await, "login failed");
+    assert_eq!(client.ask("login bob password").await, "login successful");
+    assert!(!server.rover("bob").await.bot);
+}
+
+#[test]
+fn bot_config() {
+    assert_eq!(bots::parse_config("random:2, stone,spiral:0"), Ok(vec![(Strategy::RandomWalk, 2), (Strategy::Stone, 1), (Strategy::Spiral, 0)]));
+    assert!(bots::parse_config("smart:1").is_err());
+    assert!(bots::parse_config("random:many").is_err());
+}
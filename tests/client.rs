@@ -0,0 +1,71 @@
+mod common;
+
+use common::{TestServer, ARENA};
+use ma_rs::planet::Coord;
+use ma_rs::Message;
+use ma_rs_client::{CellType, ClientError, Compass, Energy, Position, RoverClient};
+use ma_rs_protocol::server_message;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn sdk_drives_a_rover() {
+    let server = TestServer::start(&ARENA).await;
+    let mut rover = RoverClient::connect(server.addr).await.unwrap();
+
+    assert!(matches!(rover.forward().await, Err(ClientError::NotSignedIn)));
+    rover.login("bob", "password").await.unwrap();
+    server.place("bob", Coord::new(3, 3), Compass::North).await;
+
+    // commands are framed by newlines so they can be written back to back
+    let scan = rover.scan().await.unwrap();
+    assert_eq!(scan.front(), CellType::Stone);
+    rover.dig().await.unwrap();
+    rover.forward().await.unwrap();
+    assert_eq!(rover.position().await.unwrap(), Position { x: 3, y: 2, direction: Compass::North });
+
+    rover.rotate(true).await.unwrap();
+    rover.rotate(true).await.unwrap();
+    assert_eq!(rover.position().await.unwrap().direction, Compass::South);
+
+    let Energy { energy, max } = rover.energy().await.unwrap();
+    assert!(energy <= max);
+    assert_eq!(rover.scan_height().await.unwrap(), vec![0; 8]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn sdk_reports_login_errors() {
+    let server = TestServer::start(&ARENA).await;
+
+    let mut bob = RoverClient::connect(server.addr).await.unwrap();
+    bob.login("bob", "password").await.unwrap();
+
+    let mut thief = RoverClient::connect(server.addr).await.unwrap();
+    assert!(matches!(thief.login("bob", "password").await, Err(ClientError::LoginFailed)));
+
+    bob.logout().await.unwrap();
+    server.wait_offline("bob").await;
+    assert!(matches!(thief.login("bob", "wrong").await, Err(ClientError::LoginFailed)));
+    thief.login("bob", "password").await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn sdk_keeps_server_messages_apart_from_replies() {
+    let server = TestServer::start(&ARENA).await;
+    let mut rover = RoverClient::connect(server.addr).await.unwrap();
+    rover.login("bob", "password").await.unwrap();
+    server.place("bob", Coord::new(3, 3), Compass::North).await;
+
+    let author = server.game.server_uuid;
+    server.game.sessions.with(move |sessions| {
+        for session in sessions.sessions() {
+            let connection = session.connection.as_ref().unwrap();
+            for text in ["hello", "the map is\nchanging"] {
+                let _ = connection.sender.send(Message { author, target: session.uuid, data: server_message(text).into_bytes(), response: None });
+            }
+        }
+    }).await;
+
+    // the messages may arrive before, with or behind the reply
+    assert_eq!(rover.position().await.unwrap(), Position { x: 3, y: 3, direction: Compass::North });
+    assert_eq!(rover.position().await.unwrap(), Position { x: 3, y: 3, direction: Compass::North });
+    assert_eq!(rover.messages(), vec!["hello".to_owned(), "the map is changing".to_owned()]);
+}
@@ -0,0 +1,184 @@
+//! boots a real server in process on ephemeral ports and drives it with scripted tcp clients
+#![allow(dead_code, clippy::needless_return)]
+
+use std::net::SocketAddr;
+use std::time::Duration;
+use ma_rs::planet::{CellType, Coord, Planet};
+use ma_rs::rover::{Compass, Rover};
+use ma_rs::limits::Limits;
+use ma_rs::{server, GameServer, Message};
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{header, Method, Request, StatusCode};
+use axum::Router;
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tower::ServiceExt;
+use tokio::net::{TcpListener, TcpStream};
+
+pub const SEED: u64 = 6969;
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// a walled arena, the rover is placed in the middle at 3,3 with stone to the north,
+/// rock to the west, water to the south and free ground to the east
+pub const ARENA: [&str; 7] = [
+    "XXXXXXX",
+    "X     X",
+    "X  o  X",
+    "X .   X",
+    "X  W  X",
+    "X     X",
+    "XXXXXXX",
+];
+
+pub struct TestServer {
+    pub game: GameServer,
+    pub addr: SocketAddr,
+    /// the message channel of the dispatch loop, like the http api and the bots use it
+    pub sender: flume::Sender<Message>,
+}
+
+impl TestServer {
+    /// a server on a flat planet drawn by `map`, see `Planet::from_ascii`
+    pub async fn start(map: &[&str]) -> TestServer {
+        return TestServer::start_with_limits(map, Limits::default()).await;
+    }
+
+    pub async fn start_with_limits(map: &[&str], limits: Limits) -> TestServer {
+        let planet = Planet::from_ascii(SEED, &map.join("\n")).expect("the map is not valid");
+        let mut game = GameServer::new(planet);
+        game.limits = limits;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, receiver) = flume::unbounded();
+
+        tokio::spawn(server::accept(game.clone(), listener, sender.clone()));
+        let dispatch_game = game.clone();
+        tokio::spawn(async move {
+            server::dispatch(&dispatch_game, receiver, std::future::pending()).await;
+        });
+
+        return TestServer { game, addr, sender };
+    }
+
+    pub async fn connect(&self) -> TestClient {
+        let stream = TcpStream::connect(self.addr).await.unwrap();
+        return TestClient { stream, game: self.game.clone() };
+    }
+
+    /// a connected client that is logged in as `username` and stands at `coord` facing `rotation`
+    pub async fn rover_at(&self, username: &str, coord: Coord, rotation: Compass) -> TestClient {
+        let mut client = self.connect().await;
+        assert_eq!(client.ask(&format!("login {} password", username)).await, "login successful");
+        self.place(username, coord, rotation).await;
+        return client;
+    }
+
+    /// moves an online rover like an admin teleport would
+    pub async fn place(&self, username: &str, coord: Coord, rotation: Compass) {
+        let name = username.to_owned();
+        let rover = match self.game.sessions.with(move |sessions| sessions.rover(&name)).await {
+            Some((rover, true)) => rover,
+            _ => panic!("the rover is not online"),
+        };
+        let mut rover = rover.lock().await;
+
+        let planet = self.game.planet.read().await;
+        // the rover may have spawned right there
+        planet.set_celltype(rover.coord(), CellType::Air).unwrap();
+        assert_eq!(planet.replace(coord, CellType::Air, CellType::Rover), Ok(true), "rovers can only be placed on air");
+
+        rover.x = coord.x;
+        rover.y = coord.y;
+        rover.rotation = rotation;
+    }
+
+    pub async fn rover(&self, username: &str) -> Rover {
+        return self.game.find_rover(username).await.expect("there is no such rover");
+    }
+
+    pub async fn cell(&self, coord: Coord) -> CellType {
+        return self.game.planet.read().await.get_cell_type(coord);
+    }
+
+    /// waits until the rover is offline, a closed connection is only noticed by the server a moment later
+    pub async fn wait_offline(&self, username: &str) {
+        tokio::time::timeout(TIMEOUT, async {
+            loop {
+                let name = username.to_owned();
+                if let Some((_, false)) = self.game.sessions.with(move |sessions| sessions.rover(&name)).await {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        }).await.expect("the rover never went offline");
+    }
+}
+
+pub struct TestClient {
+    stream: TcpStream,
+    game: GameServer,
+}
+
+impl TestClient {
+    /// sends a command that answers and returns the answer
+    pub async fn ask(&mut self, line: &str) -> String {
+        self.stream.write_all(line.as_bytes()).await.unwrap();
+
+        let mut buffer = vec![0; 4096];
+        let n = tokio::time::timeout(TIMEOUT, self.stream.read(&mut buffer)).await
+            .unwrap_or_else(|_| panic!("no reply to {:?}", line))
+            .unwrap();
+        return String::from_utf8(buffer[..n].to_vec()).unwrap();
+    }
+
+    /// whatever the server writes next, empty once it has closed the connection
+    pub async fn read(&mut self) -> String {
+        let mut buffer = vec![0; 4096];
+        let n = tokio::time::timeout(TIMEOUT, self.stream.read(&mut buffer)).await
+            .expect("the server wrote nothing")
+            .unwrap_or(0);
+        return String::from_utf8(buffer[..n].to_vec()).unwrap();
+    }
+
+    pub async fn write(&mut self, data: impl AsRef<[u8]>) {
+        self.stream.write_all(data.as_ref()).await.unwrap();
+    }
+
+    /// sends a command that does not answer and waits until the server has handled it,
+    /// the protocol has no line endings so the next command must not be written before that
+    pub async fn send(&mut self, line: &str) {
+        let before = self.game.metrics.processed();
+        self.stream.write_all(line.as_bytes()).await.unwrap();
+
+        tokio::time::timeout(TIMEOUT, async {
+            while self.game.metrics.processed() == before {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }).await.unwrap_or_else(|_| panic!("{:?} was never handled", line));
+    }
+}
+
+/// one request through a router without a socket, the json body of the response or Null.
+/// every request comes from the address of the test server, like a browser on the same machine
+pub async fn http(router: &Router, method: Method, path: &str, token: Option<&str>, body: Option<Value>) -> (StatusCode, Value) {
+    let mut request = Request::builder().method(method).uri(path);
+    if let Some(token) = token {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+    let body = match body {
+        Some(body) => {
+            request = request.header(header::CONTENT_TYPE, "application/json");
+            Body::from(body.to_string())
+        },
+        None => Body::empty(),
+    };
+    let mut request = request.body(body).unwrap();
+    request.extensions_mut().insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 50000))));
+
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    return (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null));
+}
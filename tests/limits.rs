@@ -0,0 +1,94 @@
+mod common;
+
+use common::{TestServer, ARENA};
+use ma_rs::limits::Limits;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn commands_over_the_rate_are_rejected_until_the_client_is_dropped() {
+    let limits = Limits { rate: 1, burst: 3, strikes: 3, ..Limits::default() };
+    let server = TestServer::start_with_limits(&ARENA, limits).await;
+    let mut client = server.connect().await;
+
+    assert_eq!(client.ask("login bob secret").await, "login successful");
+    assert!(client.ask("energy").await.starts_with("Energy"));
+    assert!(client.ask("position").await.starts_with("Position"));
+
+    // commands without a reply get a message instead, so replies stay in step
+    assert_eq!(client.ask("position").await, "rate limited");
+    assert_eq!(client.ask("forward").await, "message: rate limited\n");
+
+    assert_eq!(client.ask("position").await, "rate limited");
+    assert_eq!(client.read().await, "", "the connection should be closed");
+    server.wait_offline("bob").await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn long_lines_close_the_connection() {
+    let server = TestServer::start_with_limits(&ARENA, Limits { line: 16, ..Limits::default() }).await;
+
+    let mut client = server.connect().await;
+    assert_eq!(client.ask("login bob secret").await, "login successful");
+    assert_eq!(client.ask("login somebody averylongpassword\n").await, "line too long");
+    assert_eq!(client.read().await, "");
+
+    // a line that never ends is cut off as well
+    let mut client = server.connect().await;
+    client.write("scan\nscanscan").await;
+    assert_eq!(client.read().await, "not signed in");
+    client.write("scanscanscan").await;
+    assert_eq!(client.read().await, "line too long");
+    assert_eq!(client.read().await, "");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn too_many_pending_commands_close_the_connection() {
+    let server = TestServer::start_with_limits(&ARENA, Limits { pending: 2, ..Limits::default() }).await;
+    let mut client = server.connect().await;
+
+    client.write(&"position\n".repeat(5)).await;
+    assert_eq!(client.read().await, "too many pending commands");
+    assert_eq!(client.read().await, "");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn connections_per_address_are_limited() {
+    let server = TestServer::start_with_limits(&ARENA, Limits { connections_per_ip: 2, ..Limits::default() }).await;
+    let mut first = server.connect().await;
+    let mut second = server.connect().await;
+
+    let mut third = server.connect().await;
+    assert_eq!(third.read().await, "too many connections");
+    assert_eq!(third.read().await, "");
+
+    assert_eq!(first.ask("position").await, "not signed in");
+    assert_eq!(second.ask("position").await, "not signed in");
+
+    // the slot is given back once a connection is gone
+    drop(first);
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    let mut fourth = server.connect().await;
+    assert_eq!(fourth.ask("position").await, "not signed in");
+}
+
+#[test]
+fn limits_are_configured_like_bots() {
+    let limits = Limits::parse("rate:5, ip:0").unwrap();
+    assert_eq!(limits, Limits { rate: 5, connections_per_ip: 0, ..Limits::default() });
+    assert_eq!(Limits::parse("").unwrap(), Limits::default());
+
+    assert!(Limits::parse("speed:3").is_err());
+    assert!(Limits::parse("rate").is_err());
+    assert!(Limits::parse("rate:fast").is_err());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn lines_that_are_not_utf8_are_still_answered() {
+    let server = TestServer::start_with_limits(&ARENA, Limits { pending: 2, ..Limits::default() }).await;
+    let mut client = server.connect().await;
+
+    for _ in 0..5 {
+        client.write(b"\xff\xfe\n").await;
+        // terminated, both lines may arrive in one read and an unterminated one would wait for the rest
+        assert_eq!(client.ask("position\n").await, "not signed in");
+    }
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use common::{ARENA, SEED};
+use ma_rs::planet::{CellType, Coord, Planet, PlanetError, WORLD_LIMIT};
+
+#[test]
+fn coordinates_at_the_ends_of_i32_are_outside_the_world() {
+    let planet = Planet::from_ascii(SEED, &ARENA.join("\n")).unwrap();
+
+    for coord in [Coord::new(i32::MIN, 0), Coord::new(0, i32::MIN), Coord::new(i32::MAX, 0), Coord::new(-WORLD_LIMIT, 0)] {
+        assert!(!coord.in_bounds(), "{} is in bounds", coord);
+        assert_eq!(planet.get_cell_type(coord), CellType::Bedrock);
+        planet.load_around(coord);
+        assert_eq!(planet.set_celltype(coord, CellType::Air), Err(PlanetError::OutOfBounds(coord)));
+        assert_eq!(planet.replace(coord, CellType::Bedrock, CellType::Air), Err(PlanetError::OutOfBounds(coord)));
+    }
+    assert!(Coord::new(1 - WORLD_LIMIT, WORLD_LIMIT - 1).in_bounds());
+}
+
+#[test]
+fn changing_cells_while_chunks_are_unloaded() {
+    let planet = Planet::with_seed(100, SEED);
+    let done = std::sync::atomic::AtomicBool::new(false);
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                planet.unload_distant(&[]);
+            }
+        });
+
+        for x in 0..2000 {
+            // each cell in a chunk of its own that nobody has touched yet
+            let coord = Coord::new(x * 64, 5000);
+            assert_eq!(planet.replace(coord, planet.get_cell_type(coord), CellType::Air), Ok(true), "{} changed under the test", coord);
+            planet.set_celltype(Coord::new(x * 64, -5000), CellType::Stone).unwrap();
+        }
+        done.store(true, std::sync::atomic::Ordering::Relaxed);
+    });
+
+    // a chunk dropped between the lookup and the write would come back generated, without the change
+    for x in 0..2000 {
+        assert_eq!(planet.get_cell_type(Coord::new(x * 64, 5000)), CellType::Air, "x {}", x * 64);
+        assert_eq!(planet.get_cell_type(Coord::new(x * 64, -5000)), CellType::Stone, "x {}", x * 64);
+    }
+}
+
+#[test]
+fn saved_chunks_load_again_without_rovers() {
+    let dir = std::env::temp_dir().join(format!("ma-rs-save-{}", std::process::id()));
+    let planet = Planet::with_seed(100, SEED);
+    planet.set_celltype(Coord::new(10, 10), CellType::Stone).unwrap();
+    planet.set_celltype(Coord::new(11, 10), CellType::Rover).unwrap();
+    planet.save(&dir).unwrap();
+
+    let loaded = Planet::load(&dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(loaded.seed(), SEED);
+    assert_eq!(loaded.get_cell_type(Coord::new(10, 10)), CellType::Stone);
+    assert_eq!(loaded.get_cell_type(Coord::new(11, 10)), CellType::Air);
+}
+
+#[test]
+fn version_changes_with_every_cell() {
+    let planet = Planet::from_ascii(SEED, &ARENA.join("\n")).unwrap();
+    let version = planet.version();
+
+    assert_ne!(Planet::from_ascii(SEED, &ARENA.join("\n")).unwrap().version(), version);
+    planet.set_celltype(Coord::new(1000, 1000), CellType::Air).unwrap();
+    assert_ne!(planet.version(), version);
+}
+
+#[test]
+fn cell_counts_cover_the_whole_region() {
+    let planet = Planet::with_seed(100, SEED);
+    let counts = planet.cell_counts();
+
+    assert_eq!(counts.values().sum::<usize>(), 100 * 100);
+    assert_eq!(counts.get(&CellType::Rover), None);
+}
+
+#[test]
+fn cell_counts_follow_every_change() {
+    let planet = Planet::with_seed(100, SEED);
+    planet.set_celltype(Coord::new(10, 10), CellType::Rover).unwrap();
+    planet.set_celltype(Coord::new(11, 10), CellType::Water).unwrap();
+    planet.set_celltype(Coord::new(11, 10), CellType::Air).unwrap();
+    planet.set_celltype(Coord::new(500, 500), CellType::Rover).unwrap();
+
+    let mut scanned = std::collections::HashMap::new();
+    for cell in planet.cells() {
+        *scanned.entry(cell.cell_type).or_insert(0) += 1;
+    }
+    let counts: std::collections::HashMap<CellType, usize> = planet.cell_counts().into_iter().filter(|(_, count)| *count != 0).collect();
+    assert_eq!(counts, scanned);
+    assert_eq!(counts.get(&CellType::Rover), Some(&1));
+}
+
+#[test]
+fn random_spawns_find_every_free_cell() {
+    // 45 rows of a chunk end in the middle of a word
+    let planet = Planet::with_seed(45, SEED);
+    let free = planet.cell_counts()[&CellType::Air];
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..free {
+        let spawn = planet.random_spawn(&mut rng).expect("there are free cells left");
+        assert_eq!(planet.replace(spawn, CellType::Air, CellType::Rover), Ok(true), "{} is not free", spawn);
+    }
+    assert_eq!(planet.random_spawn(&mut rng), None);
+    assert!(planet.cells().all(|cell| cell.cell_type != CellType::Air));
+}
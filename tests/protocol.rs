@@ -0,0 +1,139 @@
+mod common;
+
+use common::{TestServer, ARENA};
+use ma_rs::planet::{CellType, Coord, Planet};
+use ma_rs::rover::Compass;
+use ma_rs::{GameServer, Message};
+use uuid::Uuid;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn commands_need_a_login() {
+    let server = TestServer::start(&ARENA).await;
+    let mut client = server.connect().await;
+
+    assert_eq!(client.ask("position").await, "not signed in");
+    assert_eq!(client.ask("login bob").await, "login failed");
+    assert_eq!(client.ask("login bob secret").await, "login successful");
+    assert!(client.ask("position").await.starts_with("Position x:"));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn login_spawns_on_air_inside_the_map() {
+    let server = TestServer::start(&ARENA).await;
+    let mut client = server.connect().await;
+    assert_eq!(client.ask("login bob secret").await, "login successful");
+
+    let rover = server.rover("bob").await;
+    assert!((1..6).contains(&rover.x) && (1..6).contains(&rover.y), "spawned at {}", rover.coord());
+    assert_eq!(client.ask("position").await, format!("Position x:{} y:{} Direction:North", rover.x, rover.y));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn a_rover_can_only_be_driven_once() {
+    let server = TestServer::start(&ARENA).await;
+    let _bob = server.rover_at("bob", Coord::new(3, 3), Compass::North).await;
+
+    let mut other = server.connect().await;
+    assert_eq!(other.ask("login bob password").await, "login failed");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn reconnecting_keeps_the_rover() {
+    let server = TestServer::start(&ARENA).await;
+    let mut client = server.rover_at("bob", Coord::new(3, 3), Compass::North).await;
+    client.send("turnright").await;
+    client.send("forward").await;
+    assert_eq!(client.ask("position").await, "Position x:4 y:3 Direction:East");
+    drop(client);
+    server.wait_offline("bob").await;
+
+    let mut client = server.connect().await;
+    assert_eq!(client.ask("login bob wrong").await, "login failed");
+    assert_eq!(client.ask("login bob password").await, "login successful");
+    assert_eq!(client.ask("position").await, "Position x:4 y:3 Direction:East");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn rovers_are_restored_after_a_restart() {
+    let dir = std::env::temp_dir().join(format!("ma-rs-rovers-{}", std::process::id()));
+    let server = TestServer::start(&ARENA).await;
+    let mut client = server.rover_at("bob", Coord::new(3, 3), Compass::North).await;
+    client.send("dig").await;
+    client.send("turnright").await;
+    assert_eq!(client.ask("position").await, "Position x:3 y:3 Direction:East");
+    let before = server.rover("bob").await;
+    assert!(before.points > 0);
+    server.game.shutdown(&dir).await;
+
+    let game = GameServer::new(Planet::load(&dir).unwrap());
+    assert_eq!(game.planet.read().await.get_cell_type(Coord::new(3, 3)), CellType::Air);
+    assert_eq!(game.restore(&dir).await.unwrap(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let after = game.find_rover("bob").await.unwrap();
+    assert_eq!(after.coord(), before.coord());
+    assert_eq!((after.rotation, after.points, after.energy), (before.rotation, before.points, before.energy));
+    assert_eq!(after.explored, before.explored);
+    assert_eq!(game.planet.read().await.get_cell_type(Coord::new(3, 3)), CellType::Rover);
+    assert_eq!(game.all_rovers().await.iter().filter(|(_, online)| *online).count(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn disconnect_logs_out_but_keeps_the_connection() {
+    let server = TestServer::start(&ARENA).await;
+    let mut client = server.rover_at("bob", Coord::new(3, 3), Compass::North).await;
+
+    client.send("disconnect").await;
+    assert_eq!(client.ask("position").await, "not signed in");
+    assert_eq!(client.ask("login bob password").await, "login successful");
+    assert_eq!(client.ask("position").await, "Position x:3 y:3 Direction:North");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_logins_never_share_a_spawnpoint() {
+    let server = TestServer::start(&ARENA).await;
+    // the arena has 22 free cells
+    let mut logins = vec![];
+    for index in 0..23 {
+        let mut client = server.connect().await;
+        logins.push(tokio::spawn(async move {
+            return client.ask(&format!("login rover{} secret", index)).await;
+        }));
+    }
+
+    let mut successful = 0;
+    for login in logins {
+        if login.await.unwrap() == "login successful" {
+            successful += 1;
+        }
+    }
+    assert_eq!(successful, 22);
+
+    let mut spawnpoints = std::collections::HashSet::new();
+    for (rover, _) in server.game.all_rovers().await {
+        assert!(spawnpoints.insert(rover.coord()), "two rovers spawned at {}", rover.coord());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn unknown_sessions_get_no_worker() {
+    let server = TestServer::start(&ARENA).await;
+
+    for _ in 0..10 {
+        let (response, reply) = flume::bounded::<Message>(1);
+        server.sender.send(Message { author: Uuid::new_v4(), target: server.game.server_uuid, data: b"position".to_vec(), response: Some(response) }).unwrap();
+        assert_eq!(reply.recv_async().await.unwrap().data, b"not signed in");
+    }
+    assert_eq!(server.game.metrics.workers(), 0);
+
+    let mut client = server.connect().await;
+    assert_eq!(client.ask("position").await, "not signed in");
+    assert_eq!(server.game.metrics.workers(), 1);
+
+    drop(client);
+    tokio::time::timeout(common::TIMEOUT, async {
+        while server.game.metrics.workers() != 0 {
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+    }).await.expect("the worker of the closed connection kept running");
+}
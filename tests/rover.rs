@@ -0,0 +1,153 @@
+mod common;
+
+use common::{TestServer, ARENA, SEED};
+use ma_rs::planet::{CellType, Coord, Planet};
+use ma_rs::rover::{Compass, Rover};
+
+/// every scanned cell around 4,4 is different from its neighbours so a mixed up offset shows
+const SCAN_MAP: [&str; 9] = [
+    "XXXXXXXXX",
+    "X       X",
+    "X o.WX  X",
+    "X .o X. X",
+    "X X. .o X",
+    "X   oW. X",
+    "X WX .o X",
+    "X       X",
+    "XXXXXXXXX",
+];
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn forward_stops_at_obstacles() {
+    let server = TestServer::start(&ARENA).await;
+    let mut client = server.rover_at("bob", Coord::new(3, 3), Compass::North).await;
+
+    // stone to the north
+    client.send("forward").await;
+    assert_eq!(client.ask("position").await, "Position x:3 y:3 Direction:North");
+
+    // rock to the west
+    client.send("turnleft").await;
+    client.send("forward").await;
+    assert_eq!(client.ask("position").await, "Position x:3 y:3 Direction:West");
+
+    // water to the south
+    client.send("turnleft").await;
+    client.send("forward").await;
+    assert_eq!(client.ask("position").await, "Position x:3 y:3 Direction:South");
+
+    // two free cells to the east and then the bedrock wall
+    client.send("turnleft").await;
+    client.send("forward").await;
+    client.send("forward").await;
+    client.send("forward").await;
+    assert_eq!(client.ask("position").await, "Position x:5 y:3 Direction:East");
+
+    assert_eq!(server.cell(Coord::new(5, 3)).await, CellType::Rover);
+    assert_eq!(server.cell(Coord::new(3, 3)).await, CellType::Air);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn scan_covers_the_cells_ahead_in_every_direction() {
+    let server = TestServer::start(&SCAN_MAP).await;
+    let mut client = server.rover_at("bob", Coord::new(4, 4), Compass::North).await;
+
+    // the far row of five from left to right or top to bottom, then the near row of three
+    assert_eq!(client.ask("scan").await, "o.WX o X");
+    client.send("turnright").await;
+    assert_eq!(client.ask("scan").await, " .o.oX.W");
+    client.send("turnright").await;
+    assert_eq!(client.ask("scan").await, "WX .o oW");
+    client.send("turnright").await;
+    assert_eq!(client.ask("scan").await, "o.X Wo. ");
+    client.send("turnright").await;
+    assert_eq!(client.ask("position").await, "Position x:4 y:4 Direction:North");
+
+    // the map is flat so every height is 0
+    assert_eq!(client.ask("scanheight").await, "0 0 0 0 0 0 0 0");
+
+    let rover = server.rover("bob").await;
+    assert!(rover.explored.contains(&Coord::new(2, 2)) && rover.explored.contains(&Coord::new(6, 6)));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn dig_scores_by_cell_type() {
+    let server = TestServer::start(&ARENA).await;
+    let mut client = server.rover_at("bob", Coord::new(3, 3), Compass::North).await;
+
+    client.send("dig").await;
+    assert_eq!(server.rover("bob").await.points, 100);
+    assert_eq!(server.cell(Coord::new(3, 2)).await, CellType::Air);
+
+    client.send("turnleft").await;
+    client.send("dig").await;
+    assert_eq!(server.rover("bob").await.points, 110);
+    assert_eq!(server.cell(Coord::new(2, 3)).await, CellType::Air);
+
+    // water and air can not be dug
+    client.send("turnleft").await;
+    client.send("dig").await;
+    client.send("turnleft").await;
+    client.send("dig").await;
+    assert_eq!(server.rover("bob").await.points, 110);
+    assert_eq!(server.cell(Coord::new(3, 4)).await, CellType::Water);
+
+    // the dug out stone is free ground now
+    client.send("turnleft").await;
+    client.send("forward").await;
+    assert_eq!(client.ask("position").await, "Position x:3 y:2 Direction:North");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn only_one_rover_gets_into_a_contested_cell() {
+    let server = TestServer::start(&ARENA).await;
+    let mut alice = server.rover_at("alice", Coord::new(4, 2), Compass::South).await;
+    let mut bob = server.rover_at("bob", Coord::new(4, 4), Compass::North).await;
+
+    let (alice_position, bob_position) = tokio::join!(alice.ask("forward\nposition\n"), bob.ask("forward\nposition\n"));
+    let moved = [alice_position == "Position x:4 y:3 Direction:South", bob_position == "Position x:4 y:3 Direction:North"];
+    assert_eq!(moved.iter().filter(|moved| **moved).count(), 1, "{} and {}", alice_position, bob_position);
+
+    assert_eq!(server.cell(Coord::new(4, 3)).await, CellType::Rover);
+    let rovers = [server.rover("alice").await, server.rover("bob").await];
+    assert_ne!(rovers[0].coord(), rovers[1].coord());
+    for rover in rovers {
+        assert_eq!(server.cell(rover.coord()).await, CellType::Rover);
+    }
+}
+
+#[test]
+fn rovers_act_on_any_planet_without_a_server() {
+    let planet = Planet::from_ascii(SEED, &ARENA.join("\n")).unwrap();
+    let mut rover = Rover::new("bob".to_owned(), "password".to_owned(), 3, 3);
+    planet.set_celltype(rover.coord(), CellType::Rover).unwrap();
+    let restored = rover.clone();
+
+    assert_eq!(rover.dig(&planet), Some((CellType::Stone, 100)));
+    rover.forward(&planet).unwrap();
+    assert_eq!(rover.position(), "Position x:3 y:2 Direction:North");
+    assert_eq!(planet.get_cell_type(Coord::new(3, 3)), CellType::Air);
+
+    // a copy is not tied to the planet, it only changes when it acts itself
+    assert_eq!(restored.position(), "Position x:3 y:3 Direction:North");
+    assert_eq!(restored.points, 0);
+    assert_ne!(restored, rover);
+
+    // rovers are only in the way of each other, the old copy can not drive into the new one
+    let mut restored = restored;
+    restored.forward(&planet).unwrap();
+    assert_eq!(restored.coord(), Coord::new(3, 3));
+}
+
+#[test]
+fn map_only_shows_the_explored_cells_near_the_rover() {
+    let planet = Planet::from_ascii(SEED, &ARENA.join("\n")).unwrap();
+    let mut rover = Rover::new("bob".to_owned(), "password".to_owned(), 3, 3);
+    rover.explored.extend([Coord::new(3, 2), Coord::new(-1_000_000, 3), Coord::new(3, 1_000_000)]);
+
+    assert_eq!(rover.map(&planet), "Map x:3 y:2 width:1 height:2\no\n ");
+
+    // far away everything but the rover itself is out of range
+    rover.x = 500_000;
+    assert_eq!(rover.map(&planet), "Map x:500000 y:3 width:1 height:1\n?");
+}
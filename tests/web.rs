@@ -0,0 +1,47 @@
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use axum::http::{Method, StatusCode};
+use common::{http, TestServer, ARENA};
+use ma_rs::planet::Coord;
+use ma_rs::rover::Compass;
+use ma_rs::web;
+use serde_json::json;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn the_leaderboard_ranks_by_points_then_name() {
+    let server = TestServer::start(&ARENA).await;
+    let router = web::router(server.game.clone(), server.sender.clone(), Arc::new(AtomicBool::new(true)));
+
+    let _bob = server.rover_at("bob", Coord::new(1, 5), Compass::East).await;
+    let _alice = server.rover_at("alice", Coord::new(5, 1), Compass::East).await;
+    let mut carol = server.rover_at("carol", Coord::new(3, 3), Compass::North).await;
+    carol.send("dig").await;
+    assert_eq!(server.rover("carol").await.points, 100);
+
+    let (status, leaderboard) = http(&router, Method::GET, "/leaderboard", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(leaderboard, json!([
+        { "rank": 1, "username": "carol", "points": 100, "online": true, "bot": false },
+        { "rank": 2, "username": "alice", "points": 0, "online": true, "bot": false },
+        { "rank": 3, "username": "bob", "points": 0, "online": true, "bot": false },
+    ]));
+
+    let (status, rovers) = http(&router, Method::GET, "/rovers", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let carol = rovers.as_array().unwrap().iter().find(|rover| rover["username"] == "carol").unwrap();
+    assert_eq!((&carol["x"], &carol["y"], &carol["rotation"]), (&json!(3), &json!(3), &json!("North")));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn readyz_follows_the_flag() {
+    let server = TestServer::start(&ARENA).await;
+    let ready = Arc::new(AtomicBool::new(false));
+    let router = web::router(server.game.clone(), server.sender.clone(), ready.clone());
+
+    assert_eq!(http(&router, Method::GET, "/readyz", None, None).await.0, StatusCode::SERVICE_UNAVAILABLE);
+    ready.store(true, Ordering::Relaxed);
+    assert_eq!(http(&router, Method::GET, "/readyz", None, None).await.0, StatusCode::OK);
+    assert_eq!(http(&router, Method::GET, "/healthz", None, None).await.0, StatusCode::OK);
+}
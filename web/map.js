@@ -0,0 +1,135 @@
+// must match TILE_CELLS and MAX_ZOOM in src/maps.rs
+const TILE_CELLS = 256;
+const TILE_PIXELS = 256;
+const MAX_ZOOM = 5;
+const REFRESH_INTERVAL = 2000;
+
+window.onload = () => {
+    const canvas = document.querySelector(".tiles");
+    const zoom_label = document.querySelector(".zoom");
+    const hover = document.querySelector(".hover");
+    const ctx = canvas.getContext("2d");
+
+    let zoom = 2;
+    // the cell at the top left corner of the canvas
+    let origin = { x: 0, y: 0 };
+    let tiles = new Map();
+
+    const cell_pixels = () => TILE_PIXELS / (TILE_CELLS >> zoom);
+
+    const load_tile = async (key, url) => {
+        let tile = tiles.get(key);
+        if (tile.loading) {
+            return;
+        }
+        tile.loading = true;
+
+        try {
+            // no-cache still lets the browser revalidate with the etag
+            let response = await fetch(url, { cache: "no-cache" });
+            if (response.ok) {
+                tile.image = await createImageBitmap(await response.blob());
+                draw();
+            }
+        } catch (e) {
+            console.log("tile failed", url);
+        }
+
+        tile.loading = false;
+        tile.loaded = Date.now();
+    };
+
+    const draw = () => {
+        canvas.width = canvas.clientWidth;
+        canvas.height = canvas.clientHeight;
+        ctx.fillStyle = "#323232";
+        ctx.fillRect(0, 0, canvas.width, canvas.height);
+
+        let tile_cells = TILE_CELLS >> zoom;
+        let first_x = Math.floor(origin.x / tile_cells);
+        let first_y = Math.floor(origin.y / tile_cells);
+        let last_x = Math.floor((origin.x + canvas.width / cell_pixels()) / tile_cells);
+        let last_y = Math.floor((origin.y + canvas.height / cell_pixels()) / tile_cells);
+
+        for (let tile_y = first_y; tile_y <= last_y; tile_y++) {
+            for (let tile_x = first_x; tile_x <= last_x; tile_x++) {
+                let key = `${zoom}/${tile_x}/${tile_y}`;
+                if (!tiles.has(key)) {
+                    tiles.set(key, { image: null, loading: false, loaded: 0 });
+                }
+
+                let tile = tiles.get(key);
+                if (Date.now() - tile.loaded > REFRESH_INTERVAL) {
+                    load_tile(key, `/tiles/${key}.png`);
+                }
+
+                if (tile.image) {
+                    let x = (tile_x * tile_cells - origin.x) * cell_pixels();
+                    let y = (tile_y * tile_cells - origin.y) * cell_pixels();
+                    ctx.imageSmoothingEnabled = false;
+                    ctx.drawImage(tile.image, x, y, TILE_PIXELS, TILE_PIXELS);
+                }
+            }
+        }
+
+        zoom_label.innerText = zoom;
+    };
+
+    const set_zoom = (new_zoom, pixel_x, pixel_y) => {
+        new_zoom = Math.max(0, Math.min(MAX_ZOOM, new_zoom));
+        if (new_zoom == zoom) {
+            return;
+        }
+
+        // keep the cell under the pointer in place
+        let cell_x = origin.x + pixel_x / cell_pixels();
+        let cell_y = origin.y + pixel_y / cell_pixels();
+        zoom = new_zoom;
+        origin.x = cell_x - pixel_x / cell_pixels();
+        origin.y = cell_y - pixel_y / cell_pixels();
+
+        draw();
+    };
+
+    let dragging = null;
+    canvas.onmousedown = (event) => {
+        dragging = { x: event.offsetX, y: event.offsetY };
+    };
+    window.onmouseup = () => {
+        dragging = null;
+    };
+
+    let hovered = null;
+    canvas.onmousemove = async (event) => {
+        if (dragging) {
+            origin.x -= (event.offsetX - dragging.x) / cell_pixels();
+            origin.y -= (event.offsetY - dragging.y) / cell_pixels();
+            dragging = { x: event.offsetX, y: event.offsetY };
+            draw();
+        }
+
+        let x = Math.floor(origin.x + event.offsetX / cell_pixels());
+        let y = Math.floor(origin.y + event.offsetY / cell_pixels());
+        if (hovered && hovered.x == x && hovered.y == y) {
+            return;
+        }
+        hovered = { x, y };
+
+        let cell = await (await fetch(`/cell?x=${x}&y=${y}`)).json();
+        if (hovered.x == cell.x && hovered.y == cell.y) {
+            hover.innerText = `x:${cell.x} y:${cell.y} ${cell.cell_type}, height ${cell.height}`;
+        }
+    };
+
+    canvas.onwheel = (event) => {
+        event.preventDefault();
+        set_zoom(zoom + (event.deltaY < 0 ? 1 : -1), event.offsetX, event.offsetY);
+    };
+
+    document.querySelector(".zoom_in").onclick = () => set_zoom(zoom + 1, canvas.width / 2, canvas.height / 2);
+    document.querySelector(".zoom_out").onclick = () => set_zoom(zoom - 1, canvas.width / 2, canvas.height / 2);
+
+    window.onresize = draw;
+    setInterval(draw, REFRESH_INTERVAL);
+    draw();
+}
@@ -0,0 +1,107 @@
+const CELL_COLORS = {
+    " ": "rgb(250, 165, 0)",
+    ".": "rgb(128, 100, 64)",
+    "o": "rgb(64, 64, 64)",
+    "X": "rgb(0, 0, 0)",
+    "W": "rgb(0, 0, 255)",
+    "R": "rgb(255, 0, 0)",
+};
+
+window.onload = () => {
+    const connection = document.querySelector(".connection");
+    const username = document.querySelector(".username");
+    const password = document.querySelector(".password");
+    const scan = document.querySelector(".scan");
+    const command = document.querySelector(".command");
+    const log = document.querySelector(".log");
+
+    let socket = null;
+    let pending = [];
+
+    const write = (line) => {
+        log.innerText = line + "\n" + log.innerText;
+    };
+
+    // the far row of five first and then the near row of three, the rover is below them
+    const draw_scan = (line) => {
+        let rows = [line.slice(0, 5).split(""), [" ", ...line.slice(5, 8).split(""), " "], [" ", " ", "R", " ", " "]];
+
+        scan.replaceChildren(...rows.map((row, y) => {
+            let element = document.createElement("div");
+            for (let [x, cell] of row.entries()) {
+                let square = document.createElement("span");
+                square.className = "scan_cell";
+                let outside = y == 1 && (x == 0 || x == 4) || y == 2 && x != 2;
+                square.style.backgroundColor = outside ? "transparent" : CELL_COLORS[cell] ?? "gray";
+                element.appendChild(square);
+            }
+            return element;
+        }));
+    };
+
+    const send = (line) => {
+        if (!socket || socket.readyState != WebSocket.OPEN) {
+            write("not connected");
+            return;
+        }
+
+        write("> " + line);
+        pending.push(line.split(" ")[0]);
+        socket.send(line);
+
+        // commands like forward never get a reply
+        if (!["position", "scan", "scanheight", "energy", "map", "login"].includes(pending[pending.length - 1])) {
+            pending.pop();
+        }
+    };
+
+    const connect = () => {
+        const protocol = location.protocol === "https:" ? "wss:" : "ws:";
+        socket = new WebSocket(`${protocol}//${location.host}/play`);
+        pending = [];
+
+        socket.onopen = () => {
+            connection.innerText = "open";
+            send(`login ${username.value} ${password.value}`);
+        };
+
+        socket.onmessage = (event) => {
+            write(event.data);
+
+            // messages the server sends on its own do not answer any command
+            if (event.data.startsWith("message: ") || event.data == "kicked") {
+                return;
+            }
+
+            let sent = pending.shift();
+
+            if (sent == "scan") {
+                draw_scan(event.data);
+            }
+        };
+
+        socket.onclose = () => {
+            connection.innerText = "closed";
+            socket = null;
+        };
+    };
+
+    document.querySelector(".login_button").onclick = () => {
+        if (socket) {
+            socket.close();
+        }
+        connect();
+    };
+
+    for (let button of document.querySelectorAll("[data-command]")) {
+        button.onclick = () => send(button.dataset.command);
+    }
+
+    document.querySelector(".console").onsubmit = (event) => {
+        event.preventDefault();
+        if (command.value.trim()) {
+            send(command.value.trim());
+        }
+        command.value = "";
+    };
+}
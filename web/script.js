@@ -2,48 +2,247 @@ window.onload = async () => {
     const canvas = document.querySelector(".preview");
     const last_update = document.querySelector(".last_update");
     const aliveness = document.querySelector(".aliveness");
+    const fog_rover = document.querySelector(".fog_rover");
+    const leaderboard_rows = document.querySelector(".leaderboard_rows");
+    const overlay = document.querySelector(".overlay");
+    const show_trails = document.querySelector(".show_trails");
+    const inspect = document.querySelector(".inspect");
+    const notice = document.querySelector(".notice");
 
     const ctx = canvas.getContext("2d");
     ctx.fillStyle = "#323232";
     ctx.fillRect(0, 0, canvas.width, canvas.height);
 
-    let alive = true;
-    
-    setInterval(async () => {
-        let response;
-        try {
-            response = await fetch("/planet");
-            alive = true;
-        } catch (e) {
-            if (alive) {
-                console.log("server doid");
-                alive = false;
+    let alive = false;
+
+    // the overlay is drawn at a higher resolution than the one pixel per cell map so labels stay readable
+    const OVERLAY_SCALE = 8;
+    const DIRECTIONS = { North: [0, -1], East: [1, 0], South: [0, 1], West: [-1, 0] };
+    const octx = overlay.getContext("2d");
+    let planet_size = 0;
+    let rovers = [];
+    let inspected = null;
+
+    // bots are rovers driven by the server itself
+    const label = (rover) => rover.bot ? `${rover.username} (bot)` : rover.username;
+
+    const draw_overlay = () => {
+        overlay.width = planet_size * OVERLAY_SCALE;
+        overlay.height = planet_size * OVERLAY_SCALE;
+        octx.clearRect(0, 0, overlay.width, overlay.height);
+
+        const center = (value) => (value + 0.5) * OVERLAY_SCALE;
+
+        for (let rover of rovers) {
+            let color = rover.online ? rover.color : "gray";
+
+            if (show_trails.checked && rover.trail.length > 0) {
+                octx.strokeStyle = color;
+                octx.globalAlpha = 0.5;
+                octx.lineWidth = OVERLAY_SCALE / 4;
+                octx.beginPath();
+                octx.moveTo(center(rover.trail[0][0]), center(rover.trail[0][1]));
+                for (let [x, y] of rover.trail.slice(1)) {
+                    octx.lineTo(center(x), center(y));
+                }
+                octx.lineTo(center(rover.x), center(rover.y));
+                octx.stroke();
+                octx.globalAlpha = 1;
             }
+
+            let [dx, dy] = DIRECTIONS[rover.rotation];
+            let x = center(rover.x);
+            let y = center(rover.y);
+
+            octx.fillStyle = color;
+            octx.strokeStyle = "black";
+            octx.lineWidth = 1;
+            octx.beginPath();
+            octx.moveTo(x + dx * OVERLAY_SCALE, y + dy * OVERLAY_SCALE);
+            octx.lineTo(x - dx * OVERLAY_SCALE / 2 + dy * OVERLAY_SCALE / 2, y - dy * OVERLAY_SCALE / 2 + dx * OVERLAY_SCALE / 2);
+            octx.lineTo(x - dx * OVERLAY_SCALE / 2 - dy * OVERLAY_SCALE / 2, y - dy * OVERLAY_SCALE / 2 - dx * OVERLAY_SCALE / 2);
+            octx.closePath();
+            octx.fill();
+            octx.stroke();
+
+            octx.font = `${OVERLAY_SCALE * 1.5}px sans-serif`;
+            octx.textAlign = "center";
+            octx.fillText(label(rover), x, y - OVERLAY_SCALE * 1.5);
         }
 
-        aliveness.innerText = alive ? "alive" : "doid";
+        draw_inspect();
+    };
 
-        if(!alive) {
+    const draw_inspect = () => {
+        let rover = rovers.find((rover) => rover.username == inspected);
+        if (!rover) {
+            inspect.innerText = "click a rover to inspect it";
             return;
         }
-        
-        let json_resonse = await response.json();
 
-        let board = json_resonse.board;
-        let planet_size = json_resonse.planet_size;
+        inspect.innerText = `${label(rover)} (${rover.online ? "online" : "offline"}) at x:${rover.x} y:${rover.y} facing ${rover.rotation}, ${rover.points} points, ${rover.energy} energy`;
+    };
+
+    overlay.onclick = (event) => {
+        let rect = overlay.getBoundingClientRect();
+        let x = (event.clientX - rect.left) / rect.width * planet_size;
+        let y = (event.clientY - rect.top) / rect.height * planet_size;
 
+        let nearest = null;
+        let nearest_distance = 3;
+        for (let rover of rovers) {
+            let distance = Math.hypot(rover.x + 0.5 - x, rover.y + 0.5 - y);
+            if (distance < nearest_distance) {
+                nearest = rover;
+                nearest_distance = distance;
+            }
+        }
+
+        inspected = nearest ? nearest.username : null;
+        draw_inspect();
+    };
+
+    show_trails.onchange = draw_overlay;
+
+    const touch = () => {
+        last_update.innerText = new Date(Date.now()).toLocaleString('sv-SE', { timeZone: 'CET' });
+    };
+
+    const draw_board = (board, size) => {
+        planet_size = size;
         canvas.width = planet_size;
         canvas.height = planet_size;
 
-        for (let y = 0; y < planet_size; y++) {
-            for (let x = 0; x < planet_size; x++) {
-                let index = (x + (y * planet_size)) * 3;
+        let image = ctx.createImageData(planet_size, planet_size);
+        for (let index = 0; index < planet_size * planet_size; index++) {
+            image.data[index * 4 + 0] = board[index * 3 + 0];
+            image.data[index * 4 + 1] = board[index * 3 + 1];
+            image.data[index * 4 + 2] = board[index * 3 + 2];
+            image.data[index * 4 + 3] = 255;
+        }
+        ctx.putImageData(image, 0, 0);
+        draw_overlay();
+
+        touch();
+    };
 
-                ctx.fillStyle = `rgb(${board[index + 0]}, ${board[index + 1]}, ${board[index + 2]})`;
-                ctx.fillRect(x, y, 1, 1);
+    // the live stream is omniscient, so fog of war still polls /planet?rover=
+    const connect = () => {
+        const protocol = location.protocol === "https:" ? "wss:" : "ws:";
+        const socket = new WebSocket(`${protocol}//${location.host}/live`);
+
+        socket.onopen = () => {
+            alive = true;
+            aliveness.innerText = "alive";
+        };
+
+        socket.onmessage = (event) => {
+            let message = JSON.parse(event.data);
+
+            if (message.type == "notice") {
+                notice.innerText = `server: ${message.message}`;
+                return;
             }
+
+            if (fog_rover.value.trim()) {
+                return;
+            }
+
+            switch (message.type) {
+                case "full":
+                    draw_board(message.board, message.planet_size);
+                    break;
+                case "cell":
+                    ctx.fillStyle = `rgb(${message.color[0]}, ${message.color[1]}, ${message.color[2]})`;
+                    ctx.fillRect(message.x, message.y, 1, 1);
+                    touch();
+                    break;
+                case "rover": {
+                    let rover = rovers.find((rover) => rover.username == message.username);
+                    if (rover) {
+                        if (rover.x != message.x || rover.y != message.y) {
+                            rover.trail.push([rover.x, rover.y]);
+                        }
+                        rover.x = message.x;
+                        rover.y = message.y;
+                        rover.rotation = message.rotation;
+                        draw_overlay();
+                    }
+                    touch();
+                    break;
+                }
+            }
+        };
+
+        socket.onclose = () => {
+            if (alive) {
+                console.log("server doid");
+            }
+            alive = false;
+            aliveness.innerText = "doid";
+            setTimeout(connect, 1000);
+        };
+    };
+
+    connect();
+
+    let was_fogged = false;
+    setInterval(async () => {
+        let rover = fog_rover.value.trim();
+
+        if (!rover) {
+            // get a fresh full board from the live stream's point of view
+            if (was_fogged) {
+                was_fogged = false;
+                let response = await fetch("/planet");
+                let json_resonse = await response.json();
+                draw_board(json_resonse.board, json_resonse.planet_size);
+            }
+            return;
         }
+        was_fogged = true;
 
-        last_update.innerText = new Date(Date.now()).toLocaleString('sv-SE', { timeZone: 'CET' });
+        let response;
+        try {
+            response = await fetch(`/planet?rover=${encodeURIComponent(rover)}`);
+        } catch (e) {
+            return;
+        }
+
+        if(!response.ok) {
+            aliveness.innerText = "unknown rover";
+            return;
+        }
+
+        let json_resonse = await response.json();
+        draw_board(json_resonse.board, json_resonse.planet_size);
+    }, 1000);
+
+    setInterval(async () => {
+        try {
+            rovers = await (await fetch("/rovers")).json();
+        } catch (e) {
+            return;
+        }
+        draw_overlay();
     }, 1000);
-}
\ No newline at end of file
+
+    setInterval(async () => {
+        let leaderboard;
+        try {
+            leaderboard = await (await fetch("/leaderboard")).json();
+        } catch (e) {
+            return;
+        }
+
+        leaderboard_rows.replaceChildren(...leaderboard.map((entry) => {
+            let row = document.createElement("tr");
+            for (let value of [entry.rank, label(entry), entry.points, entry.online ? "online" : "offline"]) {
+                let cell = document.createElement("td");
+                cell.innerText = value;
+                row.appendChild(cell);
+            }
+            return row;
+        }));
+    }, 2000);
+}